@@ -0,0 +1,18 @@
+-- Dropping the old table cascades to the group memberships : they are kept aside and put back.
+CREATE TEMP TABLE group_envs_backup AS SELECT * FROM m_to_m_group_envs;
+
+CREATE TABLE environments_new (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+
+INSERT INTO environments_new (id, name, value)
+SELECT id, name, value FROM environments;
+
+DROP TABLE environments;
+ALTER TABLE environments_new RENAME TO environments;
+
+INSERT INTO m_to_m_group_envs (id, group_id, env_id)
+SELECT id, group_id, env_id FROM group_envs_backup;
+DROP TABLE group_envs_backup;
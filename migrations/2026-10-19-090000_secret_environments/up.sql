@@ -0,0 +1 @@
+ALTER TABLE environments ADD COLUMN secret BOOLEAN NOT NULL DEFAULT 0;
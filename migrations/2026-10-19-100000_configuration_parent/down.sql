@@ -0,0 +1,22 @@
+-- Dropping the old table cascades to the group links, see up.sql.
+CREATE TEMP TABLE group_configs_backup AS SELECT * FROM m_to_m_group_configs;
+
+CREATE TABLE configurations_new (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    exec INTEGER NOT NULL REFERENCES executables (id)
+);
+
+-- Children inheriting their executable take their parent's, the pointer itself is lost.
+INSERT INTO configurations_new (id, name, exec)
+SELECT c.id, c.name, COALESCE(c.exec, p.exec)
+FROM configurations c LEFT JOIN configurations p ON p.id = c.parent
+WHERE COALESCE(c.exec, p.exec) IS NOT NULL;
+
+DROP TABLE configurations;
+ALTER TABLE configurations_new RENAME TO configurations;
+
+INSERT INTO m_to_m_group_configs (id, group_id, config_id)
+SELECT id, group_id, config_id FROM group_configs_backup
+WHERE config_id IN (SELECT id FROM configurations);
+DROP TABLE group_configs_backup;
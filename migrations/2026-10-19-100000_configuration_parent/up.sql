@@ -0,0 +1,22 @@
+-- A child configuration may leave its executable empty to inherit its parent's,
+-- so the table is rebuilt with a nullable exec alongside the new parent pointer.
+-- Foreign keys are on and can't be turned off inside the migration transaction, so dropping
+-- the old table cascades to its group links : they are kept aside and put back afterwards.
+CREATE TEMP TABLE group_configs_backup AS SELECT * FROM m_to_m_group_configs;
+
+CREATE TABLE configurations_new (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    exec INTEGER REFERENCES executables (id),
+    parent INTEGER REFERENCES configurations (id) ON DELETE SET NULL
+);
+
+INSERT INTO configurations_new (id, name, exec)
+SELECT id, name, exec FROM configurations;
+
+DROP TABLE configurations;
+ALTER TABLE configurations_new RENAME TO configurations;
+
+INSERT INTO m_to_m_group_configs (id, group_id, config_id)
+SELECT id, group_id, config_id FROM group_configs_backup;
+DROP TABLE group_configs_backup;
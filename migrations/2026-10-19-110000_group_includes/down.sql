@@ -0,0 +1 @@
+DROP TABLE m_to_m_group_includes
@@ -0,0 +1,13 @@
+-- A group includes the variables of other groups, in `position` order, before its own.
+CREATE TABLE m_to_m_group_includes (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL
+    CONSTRAINT fk_group
+    REFERENCES group_environments (id)
+    ON DELETE CASCADE,
+    included_id INTEGER NOT NULL
+    CONSTRAINT fk_included
+    REFERENCES group_environments (id)
+    ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0
+)
@@ -0,0 +1,12 @@
+-- Variables set on one configuration only, applied after its groups.
+CREATE TABLE configuration_variables (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    config_id INTEGER NOT NULL
+    CONSTRAINT fk_config
+    REFERENCES configurations (id)
+    ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    secret BOOLEAN NOT NULL DEFAULT 0,
+    UNIQUE (config_id, name)
+)
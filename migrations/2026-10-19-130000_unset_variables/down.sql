@@ -0,0 +1,37 @@
+-- Dropping the old table cascades to the group memberships : they are kept aside and put back.
+CREATE TEMP TABLE group_envs_backup AS SELECT * FROM m_to_m_group_envs;
+
+CREATE TABLE environments_new (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    secret BOOLEAN NOT NULL DEFAULT 0
+);
+
+INSERT INTO environments_new (id, name, value, secret)
+SELECT id, name, value, secret FROM environments;
+
+DROP TABLE environments;
+ALTER TABLE environments_new RENAME TO environments;
+
+INSERT INTO m_to_m_group_envs (id, group_id, env_id)
+SELECT id, group_id, env_id FROM group_envs_backup;
+DROP TABLE group_envs_backup;
+
+CREATE TABLE configuration_variables_new (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    config_id INTEGER NOT NULL
+    CONSTRAINT fk_config
+    REFERENCES configurations (id)
+    ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    secret BOOLEAN NOT NULL DEFAULT 0,
+    UNIQUE (config_id, name)
+);
+
+INSERT INTO configuration_variables_new (id, config_id, name, value, secret)
+SELECT id, config_id, name, value, secret FROM configuration_variables;
+
+DROP TABLE configuration_variables;
+ALTER TABLE configuration_variables_new RENAME TO configuration_variables;
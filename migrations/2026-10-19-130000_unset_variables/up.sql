@@ -0,0 +1,3 @@
+-- An unset variable removes its name from the environment instead of setting it.
+ALTER TABLE environments ADD COLUMN unset BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE configuration_variables ADD COLUMN unset BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+ALTER TABLE environments DROP COLUMN condition;
+ALTER TABLE group_environments DROP COLUMN condition;
+ALTER TABLE executables DROP COLUMN condition;
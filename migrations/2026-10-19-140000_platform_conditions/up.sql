@@ -0,0 +1,4 @@
+-- Optional platform condition such as `os=windows` or `os=linux|macos, host=work-laptop`.
+ALTER TABLE environments ADD COLUMN condition TEXT;
+ALTER TABLE group_environments ADD COLUMN condition TEXT;
+ALTER TABLE executables ADD COLUMN condition TEXT;
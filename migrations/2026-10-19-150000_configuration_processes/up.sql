@@ -0,0 +1,17 @@
+-- Extra processes a configuration starts with the same environment, in position order.
+CREATE TABLE configuration_processes (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    config_id INTEGER NOT NULL
+    CONSTRAINT fk_config
+    REFERENCES configurations (id)
+    ON DELETE CASCADE,
+    exec_id INTEGER NOT NULL
+    CONSTRAINT fk_exec
+    REFERENCES executables (id)
+    ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    args TEXT NOT NULL DEFAULT '',
+    cwd TEXT,
+    mode TEXT,
+    stop_others BOOLEAN NOT NULL DEFAULT 0
+)
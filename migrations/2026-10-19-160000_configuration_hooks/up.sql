@@ -0,0 +1,12 @@
+-- Shell commands run before the configuration starts (`pre`) and after it exits (`post`).
+CREATE TABLE configuration_hooks (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    config_id INTEGER NOT NULL
+    CONSTRAINT fk_config
+    REFERENCES configurations (id)
+    ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    stage TEXT NOT NULL,
+    command TEXT NOT NULL,
+    on_failure TEXT NOT NULL DEFAULT 'abort'
+)
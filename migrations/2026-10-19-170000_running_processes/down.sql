@@ -0,0 +1 @@
+DROP TABLE running_processes
@@ -0,0 +1,14 @@
+-- Processes started by envmaster, with what the launch needs to be repeated.
+CREATE TABLE running_processes (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    config_id INTEGER NOT NULL
+    CONSTRAINT fk_config
+    REFERENCES configurations (id)
+    ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    pid INTEGER NOT NULL,
+    started_at TEXT NOT NULL,
+    path TEXT,
+    clear BOOLEAN NOT NULL DEFAULT 0,
+    keep TEXT NOT NULL DEFAULT ''
+)
@@ -0,0 +1 @@
+ALTER TABLE configurations DROP COLUMN single_instance;
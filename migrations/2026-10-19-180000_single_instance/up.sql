@@ -0,0 +1,2 @@
+-- What launching a configuration that is already running does : `refuse`, `notify` or `replace`.
+ALTER TABLE configurations ADD COLUMN single_instance TEXT;
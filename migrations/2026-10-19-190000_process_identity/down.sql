@@ -0,0 +1 @@
+ALTER TABLE running_processes DROP COLUMN identity;
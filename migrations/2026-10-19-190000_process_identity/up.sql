@@ -0,0 +1,3 @@
+-- What the system reported about a recorded process when it started, to tell it apart from a
+-- later process reusing its pid. Empty when unknown.
+ALTER TABLE running_processes ADD COLUMN identity TEXT NOT NULL DEFAULT '';
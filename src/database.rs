@@ -1,54 +1,39 @@
 use crate::{models::*, schema};
 use diesel::prelude::*;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use directories::ProjectDirs;
-use std::collections::HashMap;
+use log::{debug, warn};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env::current_dir;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::Path;
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
-type DbResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
-
-pub fn establish_connection() -> SqliteConnection {
-    let db_path = get_db_path();
+pub type DbResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
+pub fn establish_connection(db_path: &Path) -> SqliteConnection {
+    if let Some(parent) = db_path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+    }
     let database_url: &str = db_path.to_str().unwrap();
-    println!("db url : {}", database_url);
+    debug!("db url : {}", database_url);
     let mut conn = SqliteConnection::establish(database_url)
         .unwrap_or_else(|_| panic!("Error connecting to database url..."));
     conn.run_pending_migrations(MIGRATIONS).unwrap();
     conn
 }
 
-fn get_db_path() -> PathBuf {
-    if let Some(proj_dirs) = ProjectDirs::from("com", "Dekharen", "vimming") {
-        let local = proj_dirs.data_local_dir();
-        match local.try_exists() {
-            Ok(exists) => {
-                if !exists {
-                    std::fs::create_dir_all(local).unwrap();
-                }
-            }
-            Err(err) => panic!(
-                "An error occured while acquiring the local directories : {}",
-                err
-            ),
-        }
-        local.join("vimming_storage.db")
-    } else {
-        panic!("Could not determine database path");
-    }
-}
-
 pub fn new_configuration(
     conn: &mut SqliteConnection,
     name: &str,
-    id: &i32,
+    id: Option<i32>,
+    parent: Option<i32>,
 ) -> DbResult<Configuration> {
     let cfg = diesel::insert_into(schema::configurations::table)
         .values((
             schema::configurations::name.eq(name),
             schema::configurations::exec.eq(id),
+            schema::configurations::parent.eq(parent),
         ))
         .returning(Configuration::as_returning())
         .get_result(conn)?;
@@ -58,32 +43,52 @@ pub fn update_configuration(
     conn: &mut SqliteConnection,
     id: &i32,
     name: &str,
-    exec_id: &i32,
+    exec_id: Option<i32>,
+    parent: Option<i32>,
 ) -> DbResult<Configuration> {
+    if creates_cycle(conn, *id, parent)? {
+        return Err(format!("{} can't extend one of its own descendants.", name).into());
+    }
     let group =
         diesel::update(schema::configurations::table.filter(schema::configurations::id.eq(id)))
             .set((
                 schema::configurations::name.eq(name),
                 schema::configurations::exec.eq(exec_id),
+                schema::configurations::parent.eq(parent),
             ))
             .returning(Configuration::as_returning())
             .get_result(conn)?;
     Ok(group)
 }
-pub fn new_executable(conn: &mut SqliteConnection, name: &str, exe: &str) -> DbResult<Executable> {
+pub fn new_executable(
+    conn: &mut SqliteConnection,
+    name: &str,
+    exe: &str,
+    mode: &str,
+    condition: Option<&str>,
+) -> DbResult<Executable> {
     let exe = diesel::insert_into(schema::executables::table)
         .values((
             schema::executables::name.eq(name),
             schema::executables::executable.eq(exe),
+            schema::executables::mode.eq(mode),
+            schema::executables::condition.eq(condition),
         ))
         .returning(Executable::as_returning())
         .get_result(conn)?;
     Ok(exe)
 }
 
-pub fn new_grouped_envs(conn: &mut SqliteConnection, name: &str) -> DbResult<GroupedEnvironment> {
+pub fn new_grouped_envs(
+    conn: &mut SqliteConnection,
+    name: &str,
+    condition: Option<&str>,
+) -> DbResult<GroupedEnvironment> {
     let grpenvs = diesel::insert_into(schema::group_environments::table)
-        .values(schema::group_environments::name.eq(name))
+        .values((
+            schema::group_environments::name.eq(name),
+            schema::group_environments::condition.eq(condition),
+        ))
         .returning(GroupedEnvironment::as_returning())
         .get_result(conn)?;
     Ok(grpenvs)
@@ -92,11 +97,15 @@ pub fn update_group(
     conn: &mut SqliteConnection,
     id: &i32,
     name: &str,
+    condition: Option<&str>,
 ) -> DbResult<GroupedEnvironment> {
     let group = diesel::update(
         schema::group_environments::table.filter(schema::group_environments::id.eq(id)),
     )
-    .set((schema::group_environments::name.eq(name),))
+    .set((
+        schema::group_environments::name.eq(name),
+        schema::group_environments::condition.eq(condition),
+    ))
     .returning(GroupedEnvironment::as_returning())
     .get_result(conn)?;
     Ok(group)
@@ -106,11 +115,17 @@ pub fn update_env(
     id: &i32,
     name: &str,
     value: &str,
+    secret: bool,
+    unset: bool,
+    condition: Option<&str>,
 ) -> DbResult<Environment> {
     let env = diesel::update(schema::environments::table.filter(schema::environments::id.eq(id)))
         .set((
             schema::environments::name.eq(name),
             schema::environments::value.eq(value),
+            schema::environments::secret.eq(secret),
+            schema::environments::unset.eq(unset),
+            schema::environments::condition.eq(condition),
         ))
         .returning(Environment::as_returning())
         .get_result(conn)?;
@@ -122,22 +137,34 @@ pub fn update_exec(
     name: &str,
     exec: &str,
     mode: &str,
+    condition: Option<&str>,
 ) -> DbResult<Executable> {
     let exec = diesel::update(schema::executables::table.filter(schema::executables::id.eq(id)))
         .set((
             schema::executables::name.eq(name),
             schema::executables::executable.eq(exec),
             schema::executables::mode.eq(mode),
+            schema::executables::condition.eq(condition),
         ))
         .returning(Executable::as_returning())
         .get_result(conn)?;
     Ok(exec)
 }
-pub fn new_env(conn: &mut SqliteConnection, name: &str, value: &str) -> DbResult<Environment> {
+pub fn new_env(
+    conn: &mut SqliteConnection,
+    name: &str,
+    value: &str,
+    secret: bool,
+    unset: bool,
+    condition: Option<&str>,
+) -> DbResult<Environment> {
     let env = diesel::insert_into(schema::environments::table)
         .values((
             schema::environments::name.eq(name),
             schema::environments::value.eq(value),
+            schema::environments::secret.eq(secret),
+            schema::environments::unset.eq(unset),
+            schema::environments::condition.eq(condition),
         ))
         .returning(Environment::as_returning())
         .get_result(conn)?;
@@ -220,22 +247,29 @@ pub fn get_multiple_linked_group_env(
     Ok(res)
 }
 
+/// Links of the configurations using a group directly, see [`get_group_usage`] for the ones
+/// using it through includes and inheritance.
 pub fn get_linked_group_cfg(
     conn: &mut SqliteConnection,
-    cfg_id: &i32,
+    group_id: &i32,
 ) -> DbResult<Vec<GroupConfigLink>> {
     use schema::m_to_m_group_configs::{self as rep, table};
-    let res = table.filter(rep::dsl::group_id.eq(cfg_id)).load(conn)?;
+    let res = table.filter(rep::dsl::group_id.eq(group_id)).load(conn)?;
     Ok(res)
 }
 
 pub fn get_all(conn: &mut SqliteConnection) -> DbResult<Vec<LinkedConfiguration>> {
     use schema::configurations::table;
-    let res: Vec<(Configuration, Executable)> = table
-        .inner_join(schema::executables::table)
-        .select((Configuration::as_select(), Executable::as_select()))
-        .load(conn)?;
-    let cfgs: Vec<Configuration> = res.iter().map(|pair| pair.0.clone()).collect();
+    let cfgs: Vec<Configuration> = table.load(conn)?;
+    let linked = link_configurations(conn, cfgs)?;
+    Ok(with_ancestors(&linked, linked.clone()))
+}
+
+/// Attaches groups and their environment variables to configurations.
+fn link_configurations(
+    conn: &mut SqliteConnection,
+    cfgs: Vec<Configuration>,
+) -> DbResult<Vec<LinkedConfiguration>> {
     let linker: Vec<(GroupConfigLink, Option<GroupedEnvironment>)> =
         GroupConfigLink::belonging_to(&cfgs)
             .left_outer_join(schema::group_environments::table)
@@ -244,6 +278,18 @@ pub fn get_all(conn: &mut SqliteConnection) -> DbResult<Vec<LinkedConfiguration>
                 schema::group_environments::all_columns.nullable(),
             ))
             .load(conn)?;
+    let variables: Vec<Vec<ConfigurationVariable>> = ConfigurationVariable::belonging_to(&cfgs)
+        .order(schema::configuration_variables::name)
+        .load(conn)?
+        .grouped_by(&cfgs);
+    let processes: Vec<Vec<Process>> = Process::belonging_to(&cfgs)
+        .order(schema::configuration_processes::position)
+        .load(conn)?
+        .grouped_by(&cfgs);
+    let hooks: Vec<Vec<Hook>> = Hook::belonging_to(&cfgs)
+        .order(schema::configuration_hooks::position)
+        .load(conn)?
+        .grouped_by(&cfgs);
     let configurations_with_groups: Vec<(Configuration, Vec<GroupedEnvironment>)> = linker
         .grouped_by(&cfgs)
         .into_iter()
@@ -258,13 +304,219 @@ pub fn get_all(conn: &mut SqliteConnection) -> DbResult<Vec<LinkedConfiguration>
             )
         })
         .collect();
-    let mut mapped: HashMap<i32, GroupedEnvironment> = HashMap::new();
-    for (_, groups) in configurations_with_groups.iter() {
-        for group in groups {
-            mapped.entry(group.id).or_insert_with(|| group.clone());
+    let group_ids: Vec<i32> = configurations_with_groups
+        .iter()
+        .flat_map(|(_, groups)| groups.iter().map(|g| g.id))
+        .collect();
+    let linked_groups = get_linked_groups(conn, &group_ids)?;
+    let results: Vec<LinkedConfiguration> = configurations_with_groups
+        .iter()
+        .zip(variables)
+        .zip(processes)
+        .zip(hooks)
+        .map(|((((cfg, children), variables), processes), hooks)| {
+            let new_groups = children
+                .iter()
+                .filter_map(|group| linked_groups.get(&group.id).cloned())
+                .collect();
+            LinkedConfiguration {
+                configuration: cfg.to_owned(),
+                groups: new_groups,
+                ancestors: vec![],
+                variables: variables.into_iter().map(Environment::from).collect(),
+                processes,
+                hooks,
+            }
+        })
+        .collect();
+    Ok(results)
+}
+
+pub fn get_configuration_variables(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+) -> DbResult<Vec<Environment>> {
+    use schema::configuration_variables::{self as rep, table};
+    let vars: Vec<ConfigurationVariable> = table
+        .filter(rep::config_id.eq(config_id))
+        .order(rep::name)
+        .load(conn)?;
+    Ok(vars.into_iter().map(Environment::from).collect())
+}
+
+/// Sets one variable on a configuration, replacing the value it had. Secret values are
+/// expected encrypted already.
+pub fn set_configuration_variable(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    name: &str,
+    value: &str,
+    secret: bool,
+    unset: bool,
+) -> DbResult<Environment> {
+    use schema::configuration_variables::{self as rep, table};
+    let var = diesel::insert_into(table)
+        .values((
+            rep::config_id.eq(config_id),
+            rep::name.eq(name),
+            rep::value.eq(value),
+            rep::secret.eq(secret),
+            rep::unset.eq(unset),
+        ))
+        .on_conflict((rep::config_id, rep::name))
+        .do_update()
+        .set((
+            rep::value.eq(value),
+            rep::secret.eq(secret),
+            rep::unset.eq(unset),
+        ))
+        .returning(ConfigurationVariable::as_returning())
+        .get_result(conn)?;
+    Ok(var.into())
+}
+
+/// Replaces every variable set on a configuration.
+pub fn set_configuration_variables(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    vars: &[Environment],
+) -> DbResult<usize> {
+    use schema::configuration_variables::{self as rep, table};
+    diesel::delete(table.filter(rep::config_id.eq(config_id))).execute(conn)?;
+    let rows: Vec<_> = vars
+        .iter()
+        .map(|var| {
+            (
+                rep::config_id.eq(config_id),
+                rep::name.eq(&var.name),
+                rep::value.eq(&var.value),
+                rep::secret.eq(var.secret),
+                rep::unset.eq(var.unset),
+            )
+        })
+        .collect();
+    let inserted = diesel::insert_into(table).values(&rows).execute(conn)?;
+    Ok(inserted)
+}
+
+/// Sets the single instance policy of a configuration, see
+/// [`crate::models::SINGLE_INSTANCE_POLICIES`].
+pub fn set_configuration_single_instance(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    policy: Option<&str>,
+) -> DbResult<usize> {
+    use schema::configurations::{self as rep, table};
+    let updated = diesel::update(table.filter(rep::id.eq(config_id)))
+        .set(rep::single_instance.eq(policy))
+        .execute(conn)?;
+    Ok(updated)
+}
+
+/// Replaces the processes of a configuration, ordered as given.
+pub fn set_configuration_processes(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    processes: &[ProcessInsert],
+) -> DbResult<usize> {
+    use schema::configuration_processes::{self as rep, table};
+    diesel::delete(table.filter(rep::config_id.eq(config_id))).execute(conn)?;
+    let rows: Vec<ProcessInsert> = processes
+        .iter()
+        .enumerate()
+        .map(|(position, process)| ProcessInsert {
+            config_id,
+            position: position as i32,
+            ..process.clone()
+        })
+        .collect();
+    let inserted = diesel::insert_into(table).values(&rows).execute(conn)?;
+    Ok(inserted)
+}
+
+/// Replaces the hooks of a configuration, ordered as given.
+pub fn set_configuration_hooks(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    hooks: &[HookInsert],
+) -> DbResult<usize> {
+    use schema::configuration_hooks::{self as rep, table};
+    diesel::delete(table.filter(rep::config_id.eq(config_id))).execute(conn)?;
+    let rows: Vec<HookInsert> = hooks
+        .iter()
+        .enumerate()
+        .map(|(position, hook)| HookInsert {
+            config_id,
+            position: position as i32,
+            ..hook.clone()
+        })
+        .collect();
+    let inserted = diesel::insert_into(table).values(&rows).execute(conn)?;
+    Ok(inserted)
+}
+
+pub fn new_running_process(
+    conn: &mut SqliteConnection,
+    process: &RunningProcessInsert,
+) -> DbResult<RunningProcess> {
+    use schema::running_processes::table;
+    let res = diesel::insert_into(table)
+        .values(process)
+        .returning(RunningProcess::as_returning())
+        .get_result(conn)?;
+    Ok(res)
+}
+
+/// Recorded processes, of one configuration or of all of them.
+pub fn get_running_processes(
+    conn: &mut SqliteConnection,
+    config_id: Option<i32>,
+) -> DbResult<Vec<RunningProcess>> {
+    use schema::running_processes::{self as rep, table};
+    let mut query = table.order(rep::id).into_boxed();
+    if let Some(config_id) = config_id {
+        query = query.filter(rep::config_id.eq(config_id));
+    }
+    Ok(query.load(conn)?)
+}
+
+pub fn delete_running_processes(conn: &mut SqliteConnection, ids: &[i32]) -> DbResult<usize> {
+    use schema::running_processes::{self as rep, table};
+    let res = diesel::delete(table.filter(rep::id.eq_any(ids))).execute(conn)?;
+    Ok(res)
+}
+
+pub fn delete_configuration_variable(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    name: &str,
+) -> DbResult<usize> {
+    use schema::configuration_variables::{self as rep, table};
+    let deleted =
+        diesel::delete(table.filter(rep::config_id.eq(config_id).and(rep::name.eq(name))))
+            .execute(conn)?;
+    Ok(deleted)
+}
+
+/// Groups by id, with their variables and their includes expanded recursively. The groups
+/// included by `roots` are loaded along, but only `roots` are returned.
+pub fn get_linked_groups(
+    conn: &mut SqliteConnection,
+    roots: &[i32],
+) -> DbResult<HashMap<i32, LinkedGroups>> {
+    let includes = included_ids(conn)?;
+    let mut needed: HashSet<i32> = roots.iter().copied().collect();
+    let mut stack: Vec<i32> = roots.to_vec();
+    while let Some(id) = stack.pop() {
+        for included in includes.get(&id).into_iter().flatten() {
+            if needed.insert(*included) {
+                stack.push(*included);
+            }
         }
     }
-    let all_grouped: Vec<GroupedEnvironment> = mapped.into_values().collect();
+    let all_grouped: Vec<GroupedEnvironment> = schema::group_environments::table
+        .filter(schema::group_environments::id.eq_any(needed))
+        .load(conn)?;
     let envs: Vec<(GroupEnvsLink, Option<Environment>)> = GroupEnvsLink::belonging_to(&all_grouped)
         .left_outer_join(schema::environments::table)
         .select((
@@ -272,7 +524,6 @@ pub fn get_all(conn: &mut SqliteConnection) -> DbResult<Vec<LinkedConfiguration>
             schema::environments::all_columns.nullable(),
         ))
         .load(conn)?;
-
     let all_grouped_environments: HashMap<i32, (GroupedEnvironment, Vec<Environment>)> = envs
         .grouped_by(&all_grouped)
         .into_iter()
@@ -290,30 +541,394 @@ pub fn get_all(conn: &mut SqliteConnection) -> DbResult<Vec<LinkedConfiguration>
             )
         })
         .collect();
-    let results: Vec<LinkedConfiguration> = configurations_with_groups
-        .iter()
-        .map(|(cfg, children)| {
-            let new_groups = children
-                .iter()
-                .map(|group| {
-                    let envs = match all_grouped_environments.get(&group.id) {
-                        Some(tuple) => tuple.1.clone(),
-                        None => vec![],
-                    };
-                    LinkedGroups {
-                        group: group.to_owned(),
-                        environments: envs,
-                    }
-                })
-                .collect();
-            LinkedConfiguration {
-                configuration: cfg.to_owned(),
-                groups: new_groups,
+    let mut linked = HashMap::new();
+    for root in roots {
+        if let Some(group) = expand_group(*root, &all_grouped_environments, &includes, &mut vec![])
+        {
+            linked.insert(*root, group);
+        }
+    }
+    Ok(linked)
+}
+
+/// Builds the include tree of a group. `path` holds the groups being expanded above this one,
+/// an include pointing back at one of them is dropped : saving prevents it, so only a hand
+/// edited database could contain one.
+fn expand_group(
+    id: i32,
+    groups: &HashMap<i32, (GroupedEnvironment, Vec<Environment>)>,
+    includes: &HashMap<i32, Vec<i32>>,
+    path: &mut Vec<i32>,
+) -> Option<LinkedGroups> {
+    let (group, environments) = groups.get(&id)?;
+    path.push(id);
+    let mut expanded = vec![];
+    for included in includes.get(&id).into_iter().flatten() {
+        if path.contains(included) {
+            warn!(
+                "the group {} includes itself through group id {}, the include is \
+                 ignored.",
+                group.name, included
+            );
+            continue;
+        }
+        expanded.extend(expand_group(*included, groups, includes, path));
+    }
+    path.pop();
+    Some(LinkedGroups {
+        group: group.clone(),
+        environments: environments.clone(),
+        includes: expanded,
+    })
+}
+
+/// The links answering "where is this used", loaded once for a search or a lookup.
+struct UsageIndex {
+    groups: Vec<GroupedEnvironment>,
+    group_names: HashMap<i32, String>,
+    includes: HashMap<i32, Vec<i32>>,
+    group_envs: Vec<GroupEnvsLink>,
+    group_cfgs: Vec<GroupConfigLink>,
+    configurations: HashMap<i32, Configuration>,
+    children: HashMap<i32, Vec<i32>>,
+}
+
+impl UsageIndex {
+    fn load(conn: &mut SqliteConnection) -> DbResult<Self> {
+        let groups = get_groups(conn)?;
+        let configurations: HashMap<i32, Configuration> = get_configurations(conn)?
+            .into_iter()
+            .map(|cfg| (cfg.id, cfg))
+            .collect();
+        let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+        for cfg in configurations.values() {
+            if let Some(parent) = cfg.parent {
+                children.entry(parent).or_default().push(cfg.id);
+            }
+        }
+        Ok(UsageIndex {
+            group_names: groups.iter().map(|g| (g.id, g.name.clone())).collect(),
+            groups,
+            includes: included_ids(conn)?,
+            group_envs: schema::m_to_m_group_envs::table.load(conn)?,
+            group_cfgs: schema::m_to_m_group_configs::table.load(conn)?,
+            configurations,
+            children,
+        })
+    }
+
+    /// Groups reachable from `id` through includes with the group itself, each with the source
+    /// shown for its variables : `id`'s name, or `name > included`.
+    fn reachable_groups(&self, id: i32) -> Vec<(i32, String)> {
+        let Some(name) = self.group_names.get(&id) else {
+            return vec![];
+        };
+        let mut reached = vec![(id, name.clone())];
+        let mut seen: HashSet<i32> = HashSet::from([id]);
+        let mut stack: Vec<i32> = self.includes.get(&id).cloned().unwrap_or_default();
+        while let Some(included) = stack.pop() {
+            if !seen.insert(included) {
+                continue;
+            }
+            if let Some(included_name) = self.group_names.get(&included) {
+                reached.push((included, format!("{} > {}", name, included_name)));
+            }
+            stack.extend(self.includes.get(&included).into_iter().flatten());
+        }
+        reached
+    }
+
+    /// `config_id` and its descendants, stopping at the ones `overrides` says replace what
+    /// they'd inherit.
+    fn configuration_uses(
+        &self,
+        config_id: i32,
+        source: &str,
+        overrides: &dyn Fn(&Configuration) -> bool,
+    ) -> Vec<ConfigurationUse> {
+        let mut uses = vec![];
+        let mut stack = vec![config_id];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            let Some(cfg) = self.configurations.get(&id) else {
+                continue;
+            };
+            if !seen.insert(id) || (id != config_id && overrides(cfg)) {
+                continue;
+            }
+            let source = if id == config_id {
+                source.to_owned()
+            } else {
+                format!("{} via {}", source, self.configurations[&config_id].name)
+            };
+            uses.push(ConfigurationUse {
+                configuration_id: id,
+                configuration: cfg.name.clone(),
+                source,
+            });
+            stack.extend(self.children.get(&id).into_iter().flatten());
+        }
+        uses
+    }
+
+    /// Groups including `group_id`, and configurations launching with its variables.
+    fn group_usage(&self, group_id: i32) -> Usage {
+        let mut usage = Usage::default();
+        for group in self.groups.iter() {
+            for (reached, source) in self.reachable_groups(group.id) {
+                if reached != group_id {
+                    continue;
+                }
+                if group.id != group_id {
+                    usage.groups.push(source.clone());
+                }
+                for link in self.group_cfgs.iter().filter(|l| l.group_id == group.id) {
+                    usage.configurations.extend(self.configuration_uses(
+                        link.config_id,
+                        &source,
+                        &|_| false,
+                    ));
+                }
+            }
+        }
+        usage.sort();
+        usage
+    }
+
+    /// Groups holding `env_id`, directly or through an include, and configurations launching
+    /// with it.
+    fn environment_usage(&self, env_id: i32) -> Usage {
+        let mut usage = Usage::default();
+        for link in self.group_envs.iter().filter(|l| l.env_id == env_id) {
+            if let Some(name) = self.group_names.get(&link.group_id) {
+                usage.groups.push(name.clone());
             }
+            let group = self.group_usage(link.group_id);
+            usage.groups.extend(group.groups);
+            usage.configurations.extend(group.configurations);
+        }
+        usage.sort();
+        usage
+    }
+}
+
+/// Groups including a group, and configurations launching with its variables, whatever their
+/// platform conditions.
+pub fn get_group_usage(conn: &mut SqliteConnection, group_id: i32) -> DbResult<Usage> {
+    Ok(UsageIndex::load(conn)?.group_usage(group_id))
+}
+
+/// Groups holding a variable, and configurations launching with it.
+pub fn get_environment_usage(conn: &mut SqliteConnection, env_id: i32) -> DbResult<Usage> {
+    Ok(UsageIndex::load(conn)?.environment_usage(env_id))
+}
+
+/// Configurations launching an executable, as their own, as one of their processes, or
+/// inherited from a parent. Executables sharing its name are platform variants picked at
+/// launch, see [`get_platform_executable`], so pointing at any of them counts.
+pub fn get_executable_usage(conn: &mut SqliteConnection, exec_id: i32) -> DbResult<Usage> {
+    use schema::executables::{id, name, table};
+    let chosen = get_single_executable(conn, exec_id)?;
+    let variants: HashSet<i32> = table
+        .filter(name.eq(&chosen.name))
+        .select(id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+    let index = UsageIndex::load(conn)?;
+    let processes: Vec<Process> = schema::configuration_processes::table.load(conn)?;
+    let with_processes: HashSet<i32> = processes.iter().map(|p| p.config_id).collect();
+    let mut usage = Usage::default();
+    for cfg in index.configurations.values() {
+        if cfg.exec.is_some_and(|exec| variants.contains(&exec)) {
+            usage.configurations.extend(
+                index.configuration_uses(cfg.id, "executable", &|child| child.exec.is_some()),
+            );
+        }
+    }
+    let starting: BTreeSet<i32> = processes
+        .iter()
+        .filter(|p| variants.contains(&p.exec_id))
+        .map(|p| p.config_id)
+        .collect();
+    for config_id in starting {
+        usage
+            .configurations
+            .extend(index.configuration_uses(config_id, "process", &|child| {
+                with_processes.contains(&child.id)
+            }));
+    }
+    usage.sort();
+    Ok(usage)
+}
+
+/// Finds the variables, groups and configurations matching `pattern`. Each variable comes with
+/// the groups holding it and the configurations launching with it, whatever their platform
+/// conditions.
+pub fn search(conn: &mut SqliteConnection, pattern: &SearchPattern) -> DbResult<SearchResults> {
+    let index = UsageIndex::load(conn)?;
+    let overrides: Vec<ConfigurationVariable> = schema::configuration_variables::table
+        .order(schema::configuration_variables::name)
+        .load(conn)?;
+    let mut variables = vec![];
+    for env in get_environments(conn)? {
+        if pattern.matches(&env) {
+            variables.push(VariableUsage {
+                kind: VariableKind::Variable,
+                override_of: None,
+                usage: index.environment_usage(env.id),
+                environment: env,
+            });
+        }
+    }
+    for var in overrides {
+        let config_id = var.config_id;
+        let env = Environment::from(var);
+        if pattern.matches(&env) {
+            variables.push(VariableUsage {
+                kind: VariableKind::Override,
+                override_of: Some(config_id),
+                environment: env,
+                usage: Usage {
+                    groups: vec![],
+                    configurations: index
+                        .configuration_uses(config_id, OVERRIDE_SOURCE, &|_| false),
+                },
+            });
+        }
+    }
+    variables.sort_by(|a, b| a.environment.name.cmp(&b.environment.name));
+    let mut configurations: Vec<Configuration> = index
+        .configurations
+        .into_values()
+        .filter(|c| pattern.matches_name(&c.name))
+        .collect();
+    configurations.sort_by_key(|c| c.id);
+    Ok(SearchResults {
+        variables,
+        groups: index
+            .groups
+            .into_iter()
+            .filter(|g| pattern.matches_name(&g.name))
+            .collect(),
+        configurations,
+    })
+}
+
+pub fn get_group_includes(conn: &mut SqliteConnection) -> DbResult<Vec<GroupInclude>> {
+    use schema::m_to_m_group_includes::{self as rep, table};
+    let res = table
+        .order((rep::group_id, rep::position, rep::id))
+        .load(conn)?;
+    Ok(res)
+}
+
+/// Included group ids by including group, in order.
+fn included_ids(conn: &mut SqliteConnection) -> DbResult<HashMap<i32, Vec<i32>>> {
+    let mut includes: HashMap<i32, Vec<i32>> = HashMap::new();
+    for include in get_group_includes(conn)? {
+        includes
+            .entry(include.group_id)
+            .or_default()
+            .push(include.included_id);
+    }
+    Ok(includes)
+}
+
+/// Whether `group_id` including `included` would make a group include itself.
+pub fn include_creates_cycle(
+    conn: &mut SqliteConnection,
+    group_id: i32,
+    included: &[i32],
+) -> DbResult<bool> {
+    let mut includes = included_ids(conn)?;
+    includes.insert(group_id, included.to_vec());
+    let mut seen: HashSet<i32> = HashSet::new();
+    let mut stack: Vec<i32> = included.to_vec();
+    while let Some(id) = stack.pop() {
+        if id == group_id {
+            return Ok(true);
+        }
+        if seen.insert(id) {
+            stack.extend(includes.get(&id).into_iter().flatten());
+        }
+    }
+    Ok(false)
+}
+
+/// Replaces the groups `group_id` includes, keeping the order of `included`.
+pub fn set_group_includes(
+    conn: &mut SqliteConnection,
+    group_id: i32,
+    included: &[i32],
+) -> DbResult<usize> {
+    use schema::m_to_m_group_includes::{self as rep, table};
+    if include_creates_cycle(conn, group_id, included)? {
+        return Err("A group can't include itself, directly or through other groups.".into());
+    }
+    diesel::delete(table.filter(rep::group_id.eq(group_id))).execute(conn)?;
+    let links: Vec<GroupIncludeInsert> = included
+        .iter()
+        .enumerate()
+        .map(|(position, included_id)| GroupIncludeInsert {
+            group_id: &group_id,
+            included_id,
+            position: position as i32,
         })
         .collect();
-    Ok(results)
+    let inserted = diesel::insert_into(table).values(&links).execute(conn)?;
+    Ok(inserted)
+}
+
+/// Fills the ancestors of `cfgs` from `all`. A parent chain looping back on itself is cut where
+/// the loop starts, since saving prevents it and only a hand edited database could contain one.
+fn with_ancestors(
+    all: &[LinkedConfiguration],
+    mut cfgs: Vec<LinkedConfiguration>,
+) -> Vec<LinkedConfiguration> {
+    let by_id: HashMap<i32, &LinkedConfiguration> =
+        all.iter().map(|c| (c.configuration.id, c)).collect();
+    for cfg in cfgs.iter_mut() {
+        let mut seen = vec![cfg.configuration.id];
+        let mut parent = cfg.configuration.parent;
+        while let Some(parent_id) = parent {
+            if seen.contains(&parent_id) {
+                warn!(
+                    "the configuration {} extends itself through its parents, the \
+                     chain is cut at id {}.",
+                    cfg.configuration.name, parent_id
+                );
+                break;
+            }
+            let Some(found) = by_id.get(&parent_id) else {
+                break;
+            };
+            seen.push(parent_id);
+            cfg.ancestors.push((*found).clone());
+            parent = found.configuration.parent;
+        }
+    }
+    cfgs
 }
+
+/// Whether making `parent` the parent of `id` would make a configuration its own ancestor.
+pub fn creates_cycle(conn: &mut SqliteConnection, id: i32, parent: Option<i32>) -> DbResult<bool> {
+    let parents: HashMap<i32, Option<i32>> = get_configurations(conn)?
+        .into_iter()
+        .map(|c| (c.id, c.parent))
+        .collect();
+    let mut current = parent;
+    let mut steps = 0;
+    while let Some(ancestor) = current {
+        if ancestor == id || steps > parents.len() {
+            return Ok(true);
+        }
+        current = parents.get(&ancestor).copied().flatten();
+        steps += 1;
+    }
+    Ok(false)
+}
+
 pub fn get_groups_for_config(
     conn: &mut SqliteConnection,
     config: &Configuration,
@@ -367,6 +982,25 @@ pub fn get_single_executable(conn: &mut SqliteConnection, id: i32) -> DbResult<E
     let res = table.filter(schema::executables::id.eq(id)).first(conn)?;
     Ok(res)
 }
+/// The variant of executable `id` to launch on this platform, see
+/// [`crate::platform::select_executable`].
+pub fn get_platform_executable(conn: &mut SqliteConnection, id: i32) -> DbResult<Executable> {
+    use schema::executables::table;
+    let chosen = get_single_executable(conn, id)?;
+    let variants: Vec<Executable> = table
+        .filter(schema::executables::name.eq(&chosen.name))
+        .load(conn)?;
+    match crate::platform::select_executable(&chosen, &variants) {
+        Some(exec) => Ok(exec.clone()),
+        None => Err(format!(
+            "No variant of the executable {} applies on this platform ({} {}).",
+            chosen.name,
+            crate::platform::Platform::current().os,
+            crate::platform::Platform::current().arch,
+        )
+        .into()),
+    }
+}
 #[derive(Clone, Default, Debug)]
 struct ConfigurationFile {
     id: Option<i32>,
@@ -393,15 +1027,15 @@ fn analyze_file(string: &str) -> ConfigurationFile {
                     "id" => match someid.trim().parse::<i32>() {
                         Ok(id) => {
                             if let Some(prev_id) = file.id {
-                                println!(
-                                    "WARNING : id {} will be overriden by id {} at line {}.",
+                                warn!(
+                                    "id {} will be overriden by id {} at line {}.",
                                     prev_id, id, index
                                 );
                             }
                             file.id = Some(id);
                         }
                         Err(parse_error) => {
-                            println!(
+                            warn!(
                                 "Line {} was malformatted. The line couldn't be parsed: {}",
                                 index, parse_error
                             );
@@ -441,7 +1075,7 @@ pub fn get_config(
                                     }
 
                                     Err(_) => {
-                                        println!(
+                                        warn!(
                                             "Couldn't read the file {} despite the .envcfg \
                                              extension.",
                                             filename
@@ -481,88 +1115,14 @@ pub fn get_config(
         let n = name.unwrap();
         query.filter(schema::configurations::name.like(format!("%{}%", n)))
     };
-    let res: Vec<(Configuration, Executable)> = query
-        .inner_join(schema::executables::table)
-        .select((Configuration::as_select(), Executable::as_select()))
-        .load(conn)?;
-    let cfgs: Vec<Configuration> = res.iter().map(|pair| pair.0.clone()).collect();
-    let linker: Vec<(GroupConfigLink, Option<GroupedEnvironment>)> =
-        GroupConfigLink::belonging_to(&cfgs)
-            .left_outer_join(schema::group_environments::table)
-            .select((
-                GroupConfigLink::as_select(),
-                schema::group_environments::all_columns.nullable(),
-            ))
-            .load(conn)?;
-    let configurations_with_groups: Vec<(Configuration, Vec<GroupedEnvironment>)> = linker
-        .grouped_by(&cfgs)
-        .into_iter()
-        .zip(cfgs)
-        .map(|(links, config)| {
-            (
-                config,
-                links
-                    .into_iter()
-                    .filter_map(|(_, group)| group)
-                    .collect::<Vec<GroupedEnvironment>>(),
-            )
-        })
-        .collect();
-    let mut mapped: HashMap<i32, GroupedEnvironment> = HashMap::new();
-    for (_, groups) in configurations_with_groups.iter() {
-        for group in groups {
-            mapped.entry(group.id).or_insert_with(|| group.clone());
-        }
+    let cfgs: Vec<Configuration> = query.load(conn)?;
+    let linked = link_configurations(conn, cfgs)?;
+    if linked.iter().all(|c| c.configuration.parent.is_none()) {
+        return Ok(linked);
     }
-    let all_grouped: Vec<GroupedEnvironment> = mapped.into_values().collect();
-    let envs: Vec<(GroupEnvsLink, Option<Environment>)> = GroupEnvsLink::belonging_to(&all_grouped)
-        .left_outer_join(schema::environments::table)
-        .select((
-            GroupEnvsLink::as_select(),
-            schema::environments::all_columns.nullable(),
-        ))
-        .load(conn)?;
-
-    let all_grouped_environments: HashMap<i32, (GroupedEnvironment, Vec<Environment>)> = envs
-        .grouped_by(&all_grouped)
-        .into_iter()
-        .zip(all_grouped)
-        .map(|(links, grouped)| {
-            (
-                grouped.id,
-                (
-                    grouped,
-                    links
-                        .into_iter()
-                        .filter_map(|(_, env)| env)
-                        .collect::<Vec<Environment>>(),
-                ),
-            )
-        })
-        .collect();
-    let results: Vec<LinkedConfiguration> = configurations_with_groups
-        .iter()
-        .map(|(cfg, children)| {
-            let new_groups = children
-                .iter()
-                .map(|group| {
-                    let envs = match all_grouped_environments.get(&group.id) {
-                        Some(tuple) => tuple.1.clone(),
-                        None => vec![],
-                    };
-                    LinkedGroups {
-                        group: group.to_owned(),
-                        environments: envs,
-                    }
-                })
-                .collect();
-            LinkedConfiguration {
-                configuration: cfg.to_owned(),
-                groups: new_groups,
-            }
-        })
-        .collect();
-    Ok(results)
+    let all = get_configurations(conn)?;
+    let all = link_configurations(conn, all)?;
+    Ok(with_ancestors(&all, linked))
 }
 pub fn get_executables(conn: &mut SqliteConnection) -> DbResult<Vec<Executable>> {
     use schema::executables::table;
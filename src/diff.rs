@@ -0,0 +1,347 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    database::establish_connection,
+    models::LinkedConfiguration,
+    output::{print_json, print_table, OutputFormat},
+    secrets::{decrypt, MASK},
+    vim::{config_target, resolve_configuration},
+};
+
+/// Source shown for the variables of the current environment.
+const CURRENT_SOURCE: &str = "current shell";
+
+/// A variable on one side of a diff, with where it comes from.
+#[derive(Serialize, Debug, Clone)]
+pub struct DiffValue {
+    /// Masked for secrets.
+    pub value: String,
+    /// Configuration setting it, empty for the current environment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration: Option<String>,
+    /// Group the variable comes from, see [`crate::models::ResolvedVariable`].
+    pub source: String,
+    /// Compared instead of `value`, so secrets are compared decrypted.
+    #[serde(skip)]
+    compared: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum VariableChange {
+    Added {
+        name: String,
+        new: DiffValue,
+    },
+    Removed {
+        name: String,
+        old: DiffValue,
+    },
+    Changed {
+        name: String,
+        old: DiffValue,
+        new: DiffValue,
+    },
+}
+
+/// Variables a configuration launches with, by name. Unset ones are left out.
+fn configuration_side(cfg: &LinkedConfiguration) -> BTreeMap<String, DiffValue> {
+    cfg.resolve_sources()
+        .into_iter()
+        .filter(|var| !var.environment.unset)
+        .map(|var| {
+            let env = var.environment;
+            let (value, compared) = if env.secret {
+                let compared = decrypt(&env.value).unwrap_or_else(|_| env.value.clone());
+                (MASK.to_owned(), compared)
+            } else {
+                (env.value.clone(), env.value)
+            };
+            let value = DiffValue {
+                value,
+                configuration: Some(var.configuration),
+                source: var.source,
+                compared,
+            };
+            (env.name, value)
+        })
+        .collect()
+}
+
+/// A variable of the current environment, masked when the configuration keeps it secret.
+fn current_value(value: &str, secret: bool) -> DiffValue {
+    DiffValue {
+        value: if secret {
+            MASK.to_owned()
+        } else {
+            value.to_owned()
+        },
+        configuration: None,
+        source: CURRENT_SOURCE.to_owned(),
+        compared: value.to_owned(),
+    }
+}
+
+/// What changes from `old` to `new`, sorted by name.
+pub fn diff(
+    old: &BTreeMap<String, DiffValue>,
+    new: &BTreeMap<String, DiffValue>,
+) -> Vec<VariableChange> {
+    let mut changes = vec![];
+    for (name, old_value) in old.iter() {
+        match new.get(name) {
+            None => changes.push(VariableChange::Removed {
+                name: name.clone(),
+                old: old_value.clone(),
+            }),
+            Some(new_value) if new_value.compared != old_value.compared => {
+                changes.push(VariableChange::Changed {
+                    name: name.clone(),
+                    old: old_value.clone(),
+                    new: new_value.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, new_value) in new.iter() {
+        if !old.contains_key(name) {
+            changes.push(VariableChange::Added {
+                name: name.clone(),
+                new: new_value.clone(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    changes
+}
+
+/// What launching `cfg` changes in the current environment : the variables it adds or changes,
+/// and the ones it unsets. Variables it doesn't touch are inherited, so they aren't listed.
+pub fn diff_current(cfg: &LinkedConfiguration) -> Vec<VariableChange> {
+    let current: BTreeMap<String, String> = std::env::vars().collect();
+    let secrets: BTreeSet<String> = cfg
+        .resolve_sources()
+        .into_iter()
+        .filter(|var| var.environment.secret)
+        .map(|var| var.environment.name)
+        .collect();
+    diff_environment(
+        &current,
+        &configuration_side(cfg),
+        &cfg.unset_variables(),
+        &secrets,
+    )
+}
+
+/// [`diff_current`] against `current` : the `launched` variables and the `unset` ones it has are
+/// compared, those named in `secrets` are masked.
+fn diff_environment(
+    current: &BTreeMap<String, String>,
+    launched: &BTreeMap<String, DiffValue>,
+    unset: &[String],
+    secrets: &BTreeSet<String>,
+) -> Vec<VariableChange> {
+    let mut old = BTreeMap::new();
+    for name in launched.keys().chain(unset.iter()) {
+        if let Some(value) = current.get(name) {
+            old.insert(name.clone(), current_value(value, secrets.contains(name)));
+        }
+    }
+    diff(&old, launched)
+}
+
+impl VariableChange {
+    pub fn name(&self) -> &str {
+        match self {
+            VariableChange::Added { name, .. }
+            | VariableChange::Removed { name, .. }
+            | VariableChange::Changed { name, .. } => name,
+        }
+    }
+}
+
+fn origin(value: &DiffValue) -> String {
+    match &value.configuration {
+        Some(cfg) => format!("{} / {}", cfg, value.source),
+        None => value.source.clone(),
+    }
+}
+
+/// One line per change, `+`, `-` or `~` first, with the contributing group of each side.
+pub fn render_text(changes: &[VariableChange]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        let line = match change {
+            VariableChange::Added { name, new } => {
+                format!("+ {}={}    ({})", name, new.value, origin(new))
+            }
+            VariableChange::Removed { name, old } => {
+                format!("- {}={}    ({})", name, old.value, origin(old))
+            }
+            VariableChange::Changed { name, old, new } => format!(
+                "~ {} {} -> {}    ({} -> {})",
+                name,
+                old.value,
+                new.value,
+                origin(old),
+                origin(new)
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn table_row(change: &VariableChange) -> Vec<String> {
+    let (kind, old, new) = match change {
+        VariableChange::Added { new, .. } => ("added", None, Some(new)),
+        VariableChange::Removed { old, .. } => ("removed", Some(old), None),
+        VariableChange::Changed { old, new, .. } => ("changed", Some(old), Some(new)),
+    };
+    let value = |side: Option<&DiffValue>| side.map_or("-".to_owned(), |v| v.value.clone());
+    let from = |side: Option<&DiffValue>| side.map_or("-".to_owned(), origin);
+    vec![
+        kind.to_owned(),
+        change.name().to_owned(),
+        value(old),
+        value(new),
+        from(old),
+        from(new),
+    ]
+}
+
+/// Compares the variables of two configurations, or of one against the current environment.
+pub fn diff_command(
+    db_path: &Path,
+    first: Option<&str>,
+    second: Option<&str>,
+    current: bool,
+    format: OutputFormat,
+) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(first);
+    let first = resolve_configuration(&mut conn, id, name);
+    let changes = match second {
+        _ if current => diff_current(&first),
+        Some(second) => {
+            let (id, name) = config_target(Some(second));
+            let second = resolve_configuration(&mut conn, id, name);
+            diff(&configuration_side(&first), &configuration_side(&second))
+        }
+        None => unreachable!("clap requires a second configuration without --current"),
+    };
+    match format {
+        OutputFormat::Json => print_json("diff", &changes),
+        OutputFormat::Table => print_table(
+            &["CHANGE", "NAME", "OLD", "NEW", "FROM", "TO"],
+            &changes.iter().map(table_row).collect::<Vec<_>>(),
+        ),
+        OutputFormat::Text if changes.is_empty() => println!("No differences."),
+        OutputFormat::Text => print!("{}", render_text(&changes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: &str) -> DiffValue {
+        DiffValue {
+            value: value.to_owned(),
+            configuration: Some("cfg".to_owned()),
+            source: "group".to_owned(),
+            compared: value.to_owned(),
+        }
+    }
+
+    fn secret(decrypted: &str) -> DiffValue {
+        DiffValue {
+            compared: decrypted.to_owned(),
+            ..value(MASK)
+        }
+    }
+
+    fn side(vars: &[(&str, DiffValue)]) -> BTreeMap<String, DiffValue> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    fn summary(changes: &[VariableChange]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|change| match change {
+                VariableChange::Added { name, new } => format!("+{}={}", name, new.value),
+                VariableChange::Removed { name, old } => format!("-{}={}", name, old.value),
+                VariableChange::Changed { name, old, new } => {
+                    format!("~{}={}->{}", name, old.value, new.value)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn added_removed_and_changed() {
+        let old = side(&[("A", value("1")), ("B", value("2")), ("C", value("3"))]);
+        let new = side(&[("B", value("2")), ("C", value("4")), ("D", value("5"))]);
+        assert_eq!(summary(&diff(&old, &new)), ["-A=1", "~C=3->4", "+D=5"]);
+    }
+
+    #[test]
+    fn same_sides_have_no_changes() {
+        let vars = side(&[("A", value("1")), ("B", value("2"))]);
+        assert!(diff(&vars, &vars).is_empty());
+    }
+
+    #[test]
+    fn secrets_are_compared_decrypted() {
+        let old = side(&[("TOKEN", secret("one")), ("KEY", secret("same"))]);
+        let new = side(&[("TOKEN", secret("two")), ("KEY", secret("same"))]);
+        assert_eq!(
+            summary(&diff(&old, &new)),
+            [format!("~TOKEN={}->{}", MASK, MASK)]
+        );
+    }
+
+    #[test]
+    fn current_secrets_are_masked() {
+        let current = BTreeMap::from([("TOKEN".to_owned(), "from-shell".to_owned())]);
+        let launched = side(&[("TOKEN", secret("from-config"))]);
+        let secrets = BTreeSet::from(["TOKEN".to_owned()]);
+        let changes = diff_environment(&current, &launched, &[], &secrets);
+        assert_eq!(summary(&changes), [format!("~TOKEN={}->{}", MASK, MASK)]);
+        let rendered = render_text(&changes);
+        assert!(!rendered.contains("from-shell"));
+
+        let current = BTreeMap::from([("TOKEN".to_owned(), "from-config".to_owned())]);
+        assert!(diff_environment(&current, &launched, &[], &secrets).is_empty());
+    }
+
+    #[test]
+    fn current_unset_variables_are_removed() {
+        let current = BTreeMap::from([
+            ("EDITOR".to_owned(), "nano".to_owned()),
+            ("HOME".to_owned(), "/home/me".to_owned()),
+            ("PAGER".to_owned(), "less".to_owned()),
+        ]);
+        let launched = side(&[("EDITOR", value("vim")), ("LANG", value("C"))]);
+        let unset = ["PAGER".to_owned(), "MISSING".to_owned()];
+        let changes = diff_environment(&current, &launched, &unset, &BTreeSet::new());
+        // HOME isn't touched and MISSING isn't there to unset, neither is listed.
+        assert_eq!(
+            summary(&changes),
+            ["~EDITOR=nano->vim", "+LANG=C", "-PAGER=less"]
+        );
+        let VariableChange::Removed { old, .. } = &changes[2] else {
+            panic!("PAGER should be removed");
+        };
+        assert_eq!(old.source, CURRENT_SOURCE);
+    }
+}
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    database::{establish_connection, get_executables, new_executable},
+    doctor::resolve_executable,
+    models::Executable,
+    output::{print_json, print_table, OutputFormat},
+};
+
+/// A program worth proposing : friendly name, commands it's installed as, and launch mode.
+/// GUI programs detach, terminal ones wait for the user to quit them.
+struct Known {
+    name: &'static str,
+    commands: &'static [&'static str],
+    mode: &'static str,
+}
+
+const KNOWN: &[Known] = &[
+    Known {
+        name: "Neovim",
+        commands: &["nvim"],
+        mode: "wait",
+    },
+    Known {
+        name: "Neovide",
+        commands: &["neovide"],
+        mode: "detach",
+    },
+    Known {
+        name: "Vim",
+        commands: &["vim"],
+        mode: "wait",
+    },
+    Known {
+        name: "VS Code",
+        commands: &["code"],
+        mode: "detach",
+    },
+    Known {
+        name: "Helix",
+        commands: &["hx", "helix"],
+        mode: "wait",
+    },
+    Known {
+        name: "Emacs",
+        commands: &["emacs"],
+        mode: "detach",
+    },
+    Known {
+        name: "Zed",
+        commands: &["zed", "zeditor"],
+        mode: "detach",
+    },
+    Known {
+        name: "Bash",
+        commands: &["bash"],
+        mode: "wait",
+    },
+    Known {
+        name: "Zsh",
+        commands: &["zsh"],
+        mode: "wait",
+    },
+    Known {
+        name: "Fish",
+        commands: &["fish"],
+        mode: "wait",
+    },
+];
+
+/// An executable found on this machine, not necessarily stored yet.
+#[derive(Debug, Clone, Serialize)]
+pub struct Discovered {
+    pub name: String,
+    /// The bare command when it's in PATH, so the row stays valid on other machines.
+    pub executable: String,
+    pub mode: String,
+    /// Whether an executable with this name or command is already stored.
+    pub known: bool,
+}
+
+/// Directories where the programs above are installed without always being in PATH.
+fn well_known_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = ["/usr/local/bin", "/opt/homebrew/bin", "/snap/bin"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        let home = PathBuf::from(home);
+        dirs.push(home.join(".local/bin"));
+        dirs.push(home.join(".cargo/bin"));
+    }
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        let programs = PathBuf::from(local).join("Programs");
+        dirs.push(programs.join("Microsoft VS Code").join("bin"));
+        dirs.push(programs.join("Zed"));
+    }
+    if let Some(program_files) = std::env::var_os("ProgramFiles") {
+        let program_files = PathBuf::from(program_files);
+        dirs.push(program_files.join("Neovide"));
+        dirs.push(program_files.join("Neovim").join("bin"));
+        dirs.push(program_files.join("Microsoft VS Code").join("bin"));
+        dirs.push(program_files.join("Git").join("bin"));
+    }
+    dirs.push(PathBuf::from("/Applications/Neovide.app/Contents/MacOS"));
+    dirs.push(PathBuf::from(
+        "/Applications/Visual Studio Code.app/Contents/Resources/app/bin",
+    ));
+    dirs
+}
+
+fn find(command: &str, dirs: &[PathBuf]) -> Option<String> {
+    if resolve_executable(command).is_ok() {
+        return Some(command.to_owned());
+    }
+    dirs.iter()
+        .map(|dir| dir.join(command))
+        .find_map(|path| resolve_executable(&path.to_string_lossy()).ok())
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Scans PATH and [`well_known_dirs`] for common editors and shells.
+pub fn discover(existing: &[Executable]) -> Vec<Discovered> {
+    let dirs = well_known_dirs();
+    KNOWN
+        .iter()
+        .filter_map(|known| {
+            let executable = known.commands.iter().find_map(|c| find(c, &dirs))?;
+            let program = |exe: &str| {
+                Path::new(exe)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_lowercase())
+            };
+            let known_already = existing.iter().any(|e| {
+                e.name.eq_ignore_ascii_case(known.name)
+                    || e.executable == executable
+                    || program(&e.executable) == program(&executable)
+            });
+            Some(Discovered {
+                name: known.name.to_owned(),
+                executable,
+                mode: known.mode.to_owned(),
+                known: known_already,
+            })
+        })
+        .collect()
+}
+
+/// Stores a discovered executable.
+pub fn add_discovered(
+    conn: &mut diesel::SqliteConnection,
+    found: &Discovered,
+) -> crate::database::DbResult<Executable> {
+    new_executable(conn, &found.name, &found.executable, &found.mode, None)
+}
+
+/// Lists the editors and shells installed here, and stores the selected ones with `add`.
+/// Without names, `add` stores every one that isn't stored yet.
+pub fn discover_command(db_path: &Path, names: &[String], add: bool, format: OutputFormat) {
+    let mut conn = establish_connection(db_path);
+    let existing = get_executables(&mut conn).unwrap();
+    let mut found = discover(&existing);
+    if found.is_empty() && format == OutputFormat::Text {
+        println!("No known editor or shell found.");
+        return;
+    }
+    let selected = |d: &Discovered| {
+        names.is_empty()
+            || names
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(&d.name) || n.eq_ignore_ascii_case(&d.executable))
+    };
+    for d in found.iter_mut() {
+        if add && selected(d) && !d.known {
+            add_discovered(&mut conn, d).unwrap();
+            d.known = true;
+            if format == OutputFormat::Text {
+                println!("+ {} ({}, {})", d.name, d.executable, d.mode);
+            }
+        } else if format == OutputFormat::Text {
+            let marker = if d.known { "=" } else { " " };
+            println!("{} {} ({}, {})", marker, d.name, d.executable, d.mode);
+        }
+    }
+    match format {
+        OutputFormat::Json => print_json("discovered", &found),
+        OutputFormat::Table => print_table(
+            &["NAME", "EXECUTABLE", "MODE", "STORED"],
+            &found
+                .iter()
+                .map(|d| {
+                    vec![
+                        d.name.clone(),
+                        d.executable.clone(),
+                        d.mode.clone(),
+                        if d.known { "yes" } else { "no" }.to_owned(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::Text if !add => {
+            println!("Run with --add to store them, `=` marks the ones already stored.")
+        }
+        OutputFormat::Text => {}
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    database::{establish_connection, get_all, get_executables},
+    models::Executable,
+    output::{print_json, print_table, OutputFormat},
+    platform,
+};
+
+/// Something `doctor` found : a configuration without a variant for this platform, or an
+/// executable that can't be found.
+#[derive(Serialize)]
+struct Problem<'a> {
+    /// Configuration or executable name.
+    name: &'a str,
+    /// Command of the broken executable, empty for configurations.
+    executable: Option<&'a str>,
+    error: String,
+    used_by: Vec<&'a str>,
+}
+
+/// Finds the file `executable` launches : the path itself when it has a directory part,
+/// otherwise the first match in `PATH`. On windows, `PATHEXT` extensions are tried too.
+pub fn resolve_executable(executable: &str) -> Result<PathBuf, String> {
+    resolve_executable_in(executable, std::env::var_os("PATH").as_deref())
+}
+
+/// Same as [`resolve_executable`], searching `search_path` instead of the current `PATH`, for
+/// the `PATH` a configuration launches with.
+pub fn resolve_executable_in(
+    executable: &str,
+    search_path: Option<&OsStr>,
+) -> Result<PathBuf, String> {
+    let executable = executable.trim();
+    if executable.is_empty() {
+        return Err("the executable is empty".to_owned());
+    }
+    let path = Path::new(executable);
+    if path.components().count() > 1 || path.is_absolute() {
+        return match candidates(path).into_iter().find(|c| c.is_file()) {
+            Some(found) => check_permissions(found),
+            None if path.exists() => Err(format!("{} is not a file", executable)),
+            None => Err(format!("{} doesn't exist", executable)),
+        };
+    }
+    let Some(dirs) = search_path else {
+        return Err(format!("{} isn't a path, and PATH is not set", executable));
+    };
+    let mut denied = None;
+    for dir in std::env::split_paths(&dirs) {
+        for candidate in candidates(&dir.join(path)) {
+            if !candidate.is_file() {
+                continue;
+            }
+            match check_permissions(candidate) {
+                Ok(found) => return Ok(found),
+                Err(err) => denied = denied.or(Some(err)),
+            }
+        }
+    }
+    Err(denied.unwrap_or_else(|| format!("{} was not found in PATH", executable)))
+}
+
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![path.to_path_buf()];
+    if cfg!(windows) && path.extension().is_none() {
+        let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_owned());
+        candidates.extend(
+            exts.split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| path.with_extension(ext.trim_start_matches('.'))),
+        );
+    }
+    candidates
+}
+
+#[cfg(unix)]
+fn check_permissions(path: PathBuf) -> Result<PathBuf, String> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&path)
+        .map_err(|err| format!("{} : {}", path.display(), err))?
+        .permissions()
+        .mode();
+    if mode & 0o111 == 0 {
+        return Err(format!("{} is not executable", path.display()));
+    }
+    Ok(path)
+}
+
+#[cfg(not(unix))]
+fn check_permissions(path: PathBuf) -> Result<PathBuf, String> {
+    Ok(path)
+}
+
+/// Checks an executable as it would be launched here. Variants meant for another platform
+/// can't be checked from this machine and are left alone.
+pub fn validate_executable(exe: &Executable) -> Result<(), String> {
+    if !platform::applies(exe.condition.as_deref()) {
+        return Ok(());
+    }
+    resolve_executable(&exe.executable).map(|_| ())
+}
+
+/// Lists the executables that can't be launched from this machine, and the configurations
+/// using them.
+pub fn doctor_command(db_path: &Path, format: OutputFormat) {
+    let mut conn = establish_connection(db_path);
+    let executables = get_executables(&mut conn).unwrap();
+    let configurations = get_all(&mut conn).unwrap();
+    let mut users: HashMap<i32, Vec<&str>> = HashMap::new();
+    let mut problems = vec![];
+    for cfg in configurations.iter() {
+        let Some(chosen) = cfg
+            .executable()
+            .and_then(|id| executables.iter().find(|e| e.id == id))
+        else {
+            continue;
+        };
+        match platform::select_executable(chosen, &executables) {
+            Some(exe) => users
+                .entry(exe.id)
+                .or_default()
+                .push(&cfg.configuration.name),
+            None => problems.push(Problem {
+                name: &cfg.configuration.name,
+                executable: None,
+                error: format!("no variant of {} for this platform", chosen.name),
+                used_by: vec![],
+            }),
+        }
+    }
+    for exe in executables.iter() {
+        if !platform::applies(exe.condition.as_deref()) {
+            continue;
+        }
+        if let Err(error) = resolve_executable(&exe.executable) {
+            problems.push(Problem {
+                name: &exe.name,
+                executable: Some(&exe.executable),
+                error,
+                used_by: users.get(&exe.id).cloned().unwrap_or_default(),
+            });
+        }
+    }
+    match format {
+        OutputFormat::Json => print_json("problems", &problems),
+        OutputFormat::Table => print_table(
+            &["NAME", "EXECUTABLE", "ERROR", "USED BY"],
+            &problems
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.name.to_owned(),
+                        p.executable.unwrap_or("-").to_owned(),
+                        p.error.clone(),
+                        p.used_by.join(","),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::Text => {
+            for p in problems.iter() {
+                match p.executable {
+                    Some(exe) => println!("{} ({}) : {}", p.name, exe, p.error),
+                    None => println!("{} : {}", p.name, p.error),
+                }
+                if !p.used_by.is_empty() {
+                    println!("    used by {}", p.used_by.join(", "));
+                }
+            }
+            if problems.is_empty() {
+                println!("Every executable can be launched.");
+            } else {
+                println!("{} problems found.", problems.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{iter::Peekable, str::Chars};
+
+/// Parses the content of a `.env` file into ordered `(name, value)` pairs.
+///
+/// Supports `#` comments, an optional `export ` prefix, single quoted values (taken verbatim),
+/// double quoted values (with `\n`, `\t`, `\"` and `\\` escapes) and quoted values spanning
+/// several lines. Unquoted values end at an inline ` #` comment.
+pub fn parse(string: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = vec![];
+    let mut chars = string.chars().peekable();
+    let mut line = 1;
+    loop {
+        skip_blank(&mut chars, &mut line);
+        let Some(&c) = chars.peek() else {
+            break;
+        };
+        if c == '#' {
+            skip_line(&mut chars, &mut line);
+            continue;
+        }
+        let mut key = read_key(&mut chars);
+        if key == "export" && chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            skip_spaces(&mut chars);
+            key = read_key(&mut chars);
+        }
+        skip_spaces(&mut chars);
+        if key.is_empty() || chars.next() != Some('=') {
+            return Err(format!("Line {} is not a `NAME=value` pair.", line));
+        }
+        skip_spaces(&mut chars);
+        let start = line;
+        let value = match chars.peek() {
+            Some('\'') => {
+                chars.next();
+                read_quoted(&mut chars, '\'', &mut line)
+                    .ok_or_else(|| format!("Unterminated single quote at line {}.", start))?
+            }
+            Some('"') => {
+                chars.next();
+                read_quoted(&mut chars, '"', &mut line)
+                    .ok_or_else(|| format!("Unterminated double quote at line {}.", start))?
+            }
+            _ => read_unquoted(&mut chars),
+        };
+        skip_line(&mut chars, &mut line);
+        vars.push((key, value));
+    }
+    Ok(vars)
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+}
+
+fn skip_blank(chars: &mut Peekable<Chars>, line: &mut usize) {
+    while let Some(c) = chars.next_if(|c| c.is_whitespace()) {
+        if c == '\n' {
+            *line += 1;
+        }
+    }
+}
+
+fn skip_line(chars: &mut Peekable<Chars>, line: &mut usize) {
+    for c in chars.by_ref() {
+        if c == '\n' {
+            *line += 1;
+            break;
+        }
+    }
+}
+
+fn read_key(chars: &mut Peekable<Chars>) -> String {
+    let mut key = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+        key.push(c);
+    }
+    key
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char, line: &mut usize) -> Option<String> {
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                *line += 1;
+                value.push(c);
+            }
+            '\\' if quote == '"' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other @ ('"' | '\\' | '$') => value.push(other),
+                other => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            },
+            c if c == quote => return Some(value),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn read_unquoted(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next_if(|c| *c != '\n') {
+        if c == '#' && value.ends_with([' ', '\t']) {
+            break;
+        }
+        value.push(c);
+    }
+    value.trim_end().to_owned()
+}
+
+/// Picks variables from the current process. A selector ending with `*` matches every variable
+/// starting with what comes before it.
+pub fn from_current_env(selectors: &[String]) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| {
+            selectors
+                .iter()
+                .any(|selector| match selector.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == selector,
+                })
+        })
+        .collect();
+    vars.sort();
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn double_quotes_unescape() {
+        let vars = parse(r#"A="tab\there \"quoted\" \\ \$HOME\nnext" "#).unwrap();
+        assert_eq!(vars, pairs(&[("A", "tab\there \"quoted\" \\ $HOME\nnext")]));
+        let vars = parse(r#"B="kept \x""#).unwrap();
+        assert_eq!(vars, pairs(&[("B", "kept \\x")]));
+    }
+
+    #[test]
+    fn single_quotes_are_verbatim() {
+        let vars = parse(r#"A='no \n escapes # here'"#).unwrap();
+        assert_eq!(vars, pairs(&[("A", r"no \n escapes # here")]));
+    }
+
+    #[test]
+    fn quoted_values_span_lines() {
+        let vars = parse("KEY=\"first\nsecond\"\nOTHER='a\nb'\nLAST=c").unwrap();
+        assert_eq!(
+            vars,
+            pairs(&[("KEY", "first\nsecond"), ("OTHER", "a\nb"), ("LAST", "c")])
+        );
+    }
+
+    #[test]
+    fn export_prefix_is_dropped() {
+        let vars = parse("export A=1\nexport\tB = 2\nexport=3").unwrap();
+        assert_eq!(vars, pairs(&[("A", "1"), ("B", "2"), ("export", "3")]));
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let text = "# header\n\nA=value # comment\nB=no#comment\nC=\"quoted # kept\" # dropped\n";
+        let vars = parse(text).unwrap();
+        assert_eq!(
+            vars,
+            pairs(&[("A", "value"), ("B", "no#comment"), ("C", "quoted # kept")])
+        );
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        assert_eq!(
+            parse("A=1\n\nB='open\nstill open").unwrap_err(),
+            "Unterminated single quote at line 3."
+        );
+        assert_eq!(
+            parse("A=\"x\ny\"\nB=\"open").unwrap_err(),
+            "Unterminated double quote at line 3."
+        );
+        assert_eq!(
+            parse("A=1\nnot a pair").unwrap_err(),
+            "Line 2 is not a `NAME=value` pair."
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::{collections::BTreeMap, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{
+    database::establish_connection,
+    providers::Resolver,
+    secrets::reveal_environments,
+    vim::{config_target, resolve_configuration},
+};
+
+/// Formats a resolved configuration can be written as.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum EnvFormat {
+    /// `.env` file, values are double quoted when needed.
+    #[default]
+    Dotenv,
+    /// `docker run --env-file`, which takes values literally and can't hold newlines.
+    Docker,
+    /// systemd `EnvironmentFile=`, every value double quoted.
+    Systemd,
+    /// A flat JSON object.
+    Json,
+}
+
+fn is_plain(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-./:@%+,".contains(c))
+}
+
+fn quote(value: &str, escaped: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if escaped.contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn dotenv_value(value: &str) -> String {
+    if is_plain(value) {
+        return value.to_owned();
+    }
+    quote(value, "\\\"$").replace('\n', "\\n")
+}
+
+/// Renders variables in the requested format, sorted by name. Fails when a value can't be
+/// represented, rather than writing a file the target tool would misread.
+pub fn render(envs: &BTreeMap<String, String>, format: EnvFormat) -> Result<String, String> {
+    let mut out = String::new();
+    match format {
+        EnvFormat::Json => {
+            out = serde_json::to_string_pretty(envs).map_err(|e| e.to_string())?;
+            out.push('\n');
+        }
+        EnvFormat::Dotenv => {
+            for (name, value) in envs {
+                out += &format!("{}={}\n", name, dotenv_value(value));
+            }
+        }
+        EnvFormat::Docker => {
+            for (name, value) in envs {
+                if value.contains(['\n', '\r']) {
+                    return Err(format!(
+                        "{} spans several lines, which docker env files can't hold.",
+                        name
+                    ));
+                }
+                out += &format!("{}={}\n", name, value);
+            }
+        }
+        EnvFormat::Systemd => {
+            for (name, value) in envs {
+                out += &format!("{}={}\n", name, quote(value, "\\\"$`"));
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub fn export_command(
+    db_path: &Path,
+    target: Option<&str>,
+    format: EnvFormat,
+    output: Option<&Path>,
+    include_secrets: bool,
+) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    let envs =
+        reveal_environments(&config, include_secrets).unwrap_or_else(|err| panic!("{}", err));
+    let envs: BTreeMap<String, String> = Resolver::default()
+        .resolve_all(envs)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .into_iter()
+        .collect();
+    let string = render(&envs, format).unwrap_or_else(|err| panic!("Couldn't export : {}", err));
+    match output {
+        Some(path) => std::fs::write(path, string)
+            .unwrap_or_else(|err| panic!("Couldn't write {} : {}", path.display(), err)),
+        None => print!("{}", string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envs(vars: &[(&str, &str)]) -> BTreeMap<String, String> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dotenv_quotes_when_needed() {
+        let vars = envs(&[
+            ("PLAIN", "host:5432/db"),
+            ("SPACED", "two words"),
+            ("HASH", "a#b"),
+            ("QUOTE", "say \"hi\" to $USER"),
+            ("LINES", "first\nsecond"),
+        ]);
+        assert_eq!(
+            render(&vars, EnvFormat::Dotenv).unwrap(),
+            "HASH=\"a#b\"\n\
+             LINES=\"first\\nsecond\"\n\
+             PLAIN=host:5432/db\n\
+             QUOTE=\"say \\\"hi\\\" to \\$USER\"\n\
+             SPACED=\"two words\"\n"
+        );
+    }
+
+    #[test]
+    fn dotenv_reads_back() {
+        let vars = envs(&[
+            ("A", "two words # not a comment"),
+            ("B", "back\\slash \"quoted\"\nnext line\ttab"),
+            ("C", "$HOME"),
+        ]);
+        let string = render(&vars, EnvFormat::Dotenv).unwrap();
+        let parsed: BTreeMap<String, String> =
+            crate::dotenv::parse(&string).unwrap().into_iter().collect();
+        assert_eq!(parsed, vars);
+    }
+
+    #[test]
+    fn docker_is_literal_on_one_line() {
+        let vars = envs(&[("A", "two words \"as is\"")]);
+        assert_eq!(
+            render(&vars, EnvFormat::Docker).unwrap(),
+            "A=two words \"as is\"\n"
+        );
+        let err = render(&envs(&[("KEY", "a\nb")]), EnvFormat::Docker).unwrap_err();
+        assert!(err.starts_with("KEY spans several lines"), "{}", err);
+        assert!(render(&envs(&[("KEY", "a\rb")]), EnvFormat::Docker).is_err());
+    }
+
+    #[test]
+    fn systemd_always_quotes() {
+        let vars = envs(&[("PLAIN", "value"), ("SPECIAL", "\"$x\" `cmd` \\")]);
+        assert_eq!(
+            render(&vars, EnvFormat::Systemd).unwrap(),
+            "PLAIN=\"value\"\nSPECIAL=\"\\\"\\$x\\\" \\`cmd\\` \\\\\"\n"
+        );
+    }
+
+    #[test]
+    fn json_is_a_flat_object() {
+        let vars = envs(&[("B", "line\n\"two\""), ("A", "1")]);
+        let string = render(&vars, EnvFormat::Json).unwrap();
+        assert!(string.ends_with("}\n"));
+        assert!(string.find("\"A\"").unwrap() < string.find("\"B\"").unwrap());
+        let parsed: BTreeMap<String, String> = serde_json::from_str(&string).unwrap();
+        assert_eq!(parsed, vars);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::warn;
+
+use crate::{
+    database::establish_connection,
+    store::store_dir,
+    vim::{config_target, resolve_configuration},
+};
+
+/// Output files kept per configuration, the oldest are removed past it.
+const KEPT_LAUNCH_LOGS: usize = 20;
+/// Output files older than this are removed whatever their count.
+const MAX_LAUNCH_LOG_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Separates the launch time from the process name in file names. [`file_name_part`] replaces
+/// it, so a process name can't end with another one's suffix.
+const NAME_SEPARATOR: char = '.';
+
+fn file_name_part(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Directory holding the output of the detached processes of `config`, in the directory of the
+/// database at `db_path` so stores with a configuration of the same name don't mix.
+pub fn launch_log_dir(db_path: &Path, config: &str) -> PathBuf {
+    store_dir(db_path).join("logs").join(file_name_part(config))
+}
+
+/// Suffix of the output files of `process`.
+fn process_suffix(process: &str) -> String {
+    format!("{}{}.log", NAME_SEPARATOR, file_name_part(process))
+}
+
+/// Creates the file a detached process of `config` writes its stdout and stderr to, named after
+/// the launch time and the process.
+pub fn create_launch_log(
+    db_path: &Path,
+    config: &str,
+    process: &str,
+) -> std::io::Result<(PathBuf, File)> {
+    let dir = launch_log_dir(db_path, config);
+    std::fs::create_dir_all(&dir)?;
+    let stamp = humantime::format_rfc3339_millis(SystemTime::now())
+        .to_string()
+        .replace(':', "-");
+    let path = dir.join(format!("{}{}", stamp, process_suffix(process)));
+    let file = File::create(&path)?;
+    prune(&dir);
+    Ok((path, file))
+}
+
+/// Output files in `dir`, oldest first. The names start with the launch time, so they sort
+/// chronologically.
+fn logs_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    logs.sort();
+    logs
+}
+
+/// Output files of `config`, oldest first.
+pub fn launch_logs(db_path: &Path, config: &str) -> Vec<PathBuf> {
+    logs_in(&launch_log_dir(db_path, config))
+}
+
+/// Applies [`KEPT_LAUNCH_LOGS`] and [`MAX_LAUNCH_LOG_AGE`] to the files of `dir`.
+fn prune(dir: &Path) {
+    let logs = logs_in(dir);
+    let excess = logs.len().saturating_sub(KEPT_LAUNCH_LOGS);
+    for (index, path) in logs.iter().enumerate() {
+        let expired = std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > MAX_LAUNCH_LOG_AGE);
+        if index < excess || expired {
+            if let Err(err) = std::fs::remove_file(path) {
+                warn!(
+                    "couldn't remove the old output {} : {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+fn last_lines(string: &str, count: usize) -> String {
+    let lines: Vec<&str> = string.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    lines[start..]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Prints the output of the latest detached launch of a configuration, or lists them all.
+pub fn logs_command(
+    db_path: &Path,
+    target: Option<&str>,
+    process: Option<&str>,
+    lines: Option<usize>,
+    follow: bool,
+    list: bool,
+) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    drop(conn);
+    let name = &config.configuration.name;
+    let mut logs = launch_logs(db_path, name);
+    if let Some(process) = process {
+        let suffix = process_suffix(process);
+        logs.retain(|path| path.to_string_lossy().ends_with(&suffix));
+    }
+    if list {
+        for path in logs.iter() {
+            println!("{}", path.display());
+        }
+        return;
+    }
+    let Some(latest) = logs.last() else {
+        println!(
+            "No output was captured for {} yet, only detached processes write one.",
+            name
+        );
+        return;
+    };
+    let mut file = File::open(latest)
+        .unwrap_or_else(|err| panic!("Couldn't open {} : {}", latest.display(), err));
+    let mut content = vec![];
+    file.read_to_end(&mut content)
+        .unwrap_or_else(|err| panic!("Couldn't read {} : {}", latest.display(), err));
+    let text = String::from_utf8_lossy(&content);
+    match lines {
+        Some(count) => print!("{}", last_lines(&text, count)),
+        None => print!("{}", text),
+    }
+    if !follow {
+        return;
+    }
+    let mut position = content.len() as u64;
+    loop {
+        std::thread::sleep(Duration::from_millis(250));
+        let len = match std::fs::metadata(latest) {
+            Ok(meta) => meta.len(),
+            Err(_) => return,
+        };
+        if len < position {
+            // Truncated, start over.
+            position = 0;
+        }
+        if len == position {
+            continue;
+        }
+        let mut chunk = vec![];
+        if file.seek(SeekFrom::Start(position)).is_ok() && file.read_to_end(&mut chunk).is_ok() {
+            print!("{}", String::from_utf8_lossy(&chunk));
+            let _ = std::io::stdout().flush();
+            position += chunk.len() as u64;
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, path::Path};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    database::{
+        establish_connection, get_all, get_configurations, get_environments, get_envs_for_group,
+        get_executables, get_group_includes, get_groups,
+    },
+    models::{Configuration, GroupedEnvironment},
+    output::{print_json, print_table, OutputFormat},
+    secrets::{display_assignment, display_value},
+};
+
+/// What `list` prints.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListKind {
+    Configurations,
+    Groups,
+    Variables,
+    Executables,
+}
+
+/// A configuration with the names it points to, as listed.
+#[derive(Serialize)]
+struct ConfigurationSummary<'a> {
+    #[serde(flatten)]
+    configuration: &'a Configuration,
+    /// Name of `exec`, empty when inherited.
+    executable: Option<&'a str>,
+    /// Name of `parent`.
+    extends: Option<&'a str>,
+    /// Groups linked to the configuration itself, without the inherited ones.
+    groups: Vec<String>,
+}
+
+/// A group with its includes and variable names, as listed.
+#[derive(Serialize)]
+struct GroupSummary<'a> {
+    #[serde(flatten)]
+    group: &'a GroupedEnvironment,
+    includes: Vec<&'a str>,
+    variables: Vec<String>,
+}
+
+fn or_dash(value: Option<&str>) -> String {
+    value.unwrap_or("-").to_owned()
+}
+
+/// Prints the stored configurations, groups, variables or executables.
+pub fn list_command(db_path: &Path, kind: ListKind, format: OutputFormat) {
+    let mut conn = establish_connection(db_path);
+    match kind {
+        ListKind::Configurations => {
+            let configurations = get_configurations(&mut conn).unwrap();
+            let executables: HashMap<i32, String> = get_executables(&mut conn)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.id, e.name))
+                .collect();
+            let names: HashMap<i32, &str> = configurations
+                .iter()
+                .map(|c| (c.id, c.name.as_str()))
+                .collect();
+            let mut groups: HashMap<i32, Vec<String>> = HashMap::new();
+            for cfg in get_all(&mut conn).unwrap() {
+                groups.insert(
+                    cfg.configuration.id,
+                    cfg.groups.iter().map(|g| g.group.name.clone()).collect(),
+                );
+            }
+            let summaries: Vec<ConfigurationSummary> = configurations
+                .iter()
+                .map(|cfg| ConfigurationSummary {
+                    configuration: cfg,
+                    executable: cfg
+                        .exec
+                        .and_then(|id| executables.get(&id))
+                        .map(String::as_str),
+                    extends: cfg.parent.and_then(|id| names.get(&id).copied()),
+                    groups: groups.remove(&cfg.id).unwrap_or_default(),
+                })
+                .collect();
+            match format {
+                OutputFormat::Json => print_json("configurations", &summaries),
+                OutputFormat::Table => print_table(
+                    &["ID", "NAME", "EXECUTABLE", "EXTENDS", "GROUPS"],
+                    &summaries
+                        .iter()
+                        .map(|s| {
+                            vec![
+                                s.configuration.id.to_string(),
+                                s.configuration.name.clone(),
+                                or_dash(s.executable),
+                                or_dash(s.extends),
+                                s.groups.join(","),
+                            ]
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                OutputFormat::Text => {
+                    for s in summaries.iter() {
+                        print!("{} {}", s.configuration.id, s.configuration.name);
+                        if let Some(exe) = s.executable {
+                            print!(" ({})", exe);
+                        }
+                        if let Some(parent) = s.extends {
+                            print!(" extends {}", parent);
+                        }
+                        if !s.groups.is_empty() {
+                            print!(" : {}", s.groups.join(", "));
+                        }
+                        println!();
+                    }
+                }
+            }
+        }
+        ListKind::Groups => {
+            let groups = get_groups(&mut conn).unwrap();
+            let names: HashMap<i32, &str> =
+                groups.iter().map(|g| (g.id, g.name.as_str())).collect();
+            let mut includes: HashMap<i32, Vec<&str>> = HashMap::new();
+            for include in get_group_includes(&mut conn).unwrap() {
+                if let Some(name) = names.get(&include.included_id) {
+                    includes.entry(include.group_id).or_default().push(name);
+                }
+            }
+            let mut summaries = vec![];
+            for group in groups.iter() {
+                let mut variables: Vec<String> = get_envs_for_group(&mut conn, group)
+                    .unwrap()
+                    .into_iter()
+                    .map(|env| env.name)
+                    .collect();
+                variables.sort();
+                summaries.push(GroupSummary {
+                    group,
+                    includes: includes.remove(&group.id).unwrap_or_default(),
+                    variables,
+                });
+            }
+            match format {
+                OutputFormat::Json => print_json("groups", &summaries),
+                OutputFormat::Table => print_table(
+                    &["ID", "NAME", "CONDITION", "INCLUDES", "VARIABLES"],
+                    &summaries
+                        .iter()
+                        .map(|s| {
+                            vec![
+                                s.group.id.to_string(),
+                                s.group.name.clone(),
+                                or_dash(s.group.condition.as_deref()),
+                                s.includes.join(","),
+                                s.variables.join(","),
+                            ]
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                OutputFormat::Text => {
+                    for s in summaries.iter() {
+                        println!("{} {}", s.group.id, s.group.name);
+                        if !s.includes.is_empty() {
+                            println!("    includes {}", s.includes.join(", "));
+                        }
+                        if !s.variables.is_empty() {
+                            println!("    {}", s.variables.join(", "));
+                        }
+                    }
+                }
+            }
+        }
+        ListKind::Variables => {
+            let mut variables = get_environments(&mut conn).unwrap();
+            variables.sort_by(|a, b| a.name.cmp(&b.name));
+            match format {
+                OutputFormat::Json => print_json("variables", &variables),
+                OutputFormat::Table => print_table(
+                    &["ID", "NAME", "VALUE", "CONDITION"],
+                    &variables
+                        .iter()
+                        .map(|env| {
+                            vec![
+                                env.id.to_string(),
+                                env.name.clone(),
+                                display_value(env).to_owned(),
+                                or_dash(env.condition.as_deref()),
+                            ]
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                OutputFormat::Text => {
+                    for env in variables.iter() {
+                        println!("{} {}", env.id, display_assignment(env));
+                    }
+                }
+            }
+        }
+        ListKind::Executables => {
+            let executables = get_executables(&mut conn).unwrap();
+            match format {
+                OutputFormat::Json => print_json("executables", &executables),
+                OutputFormat::Table => print_table(
+                    &["ID", "NAME", "EXECUTABLE", "MODE", "CONDITION"],
+                    &executables
+                        .iter()
+                        .map(|exe| {
+                            vec![
+                                exe.id.to_string(),
+                                exe.name.clone(),
+                                exe.executable.clone(),
+                                exe.mode.clone(),
+                                or_dash(exe.condition.as_deref()),
+                            ]
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                OutputFormat::Text => {
+                    for exe in executables.iter() {
+                        println!("{} {} ({}, {})", exe.id, exe.name, exe.executable, exe.mode);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,104 @@
-use log::{debug, error, info, trace, warn};
-use std::time::SystemTime;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
-fn setup_logger() -> Result<(), fern::InitError> {
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "[{} {} {}] {}",
-                humantime::format_rfc3339_seconds(SystemTime::now()),
-                record.level(),
-                record.target(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Debug)
-        .chain(std::io::stdout())
-        .chain(fern::log_file("output.log")?)
-        .apply()?;
-    Ok(())
+/// Target of the records only written to the log file : launches, hook output, exits.
+pub const LAUNCH_TARGET: &str = "launch";
+const LOG_FILE_NAME: &str = "envmaster.log";
+/// Size after which the log file is rotated, when the program starts.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// Rotated files kept, as `envmaster.log.1` (newest) to `envmaster.log.3`.
+const KEPT_LOGS: usize = 3;
+
+/// Log file used with the database at `db_path`, in its local directory rather than next to it :
+/// the database may sit in a shared repository, and the log holds hook output and launch details.
+pub fn log_path(db_path: &Path) -> PathBuf {
+    crate::store::store_dir(db_path).join(LOG_FILE_NAME)
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    setup_logger()?;
+/// Shifts `path` to `path.1`, `path.1` to `path.2`... once it's grown past [`MAX_LOG_SIZE`].
+fn rotate(path: &Path) -> std::io::Result<()> {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.len() >= MAX_LOG_SIZE => {}
+        _ => return Ok(()),
+    }
+    for index in (1..KEPT_LOGS).rev() {
+        let from = rotated(path, index);
+        if from.exists() {
+            std::fs::rename(&from, rotated(path, index + 1))?;
+        }
+    }
+    std::fs::rename(path, rotated(path, 1))
+}
 
-    info!("Hello, world!");
-    warn!("Warning!");
-    debug!("Now exiting.");
+/// Console level for `-v`/`-q` counts : warnings and up by default, each `-v` adds a level and
+/// each `-q` removes one.
+fn console_level(verbosity: i8) -> log::LevelFilter {
+    match verbosity {
+        i8::MIN..=-2 => log::LevelFilter::Off,
+        -1 => log::LevelFilter::Error,
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
 
+/// Logs to stderr at the level picked by `verbosity`, and everything from debug up to the log
+/// file of the database, see [`log_path`]. Records targeting [`LAUNCH_TARGET`] only go to the file.
+pub fn setup_logger(verbosity: i8, db_path: &Path) -> Result<(), fern::InitError> {
+    let console = fern::Dispatch::new()
+        .format(|out, message, record| out.finish(format_args!("{} : {}", record.level(), message)))
+        .level(console_level(verbosity))
+        .filter(|metadata| metadata.target() != LAUNCH_TARGET)
+        .chain(std::io::stderr());
+    let mut dispatch = fern::Dispatch::new().chain(console);
+    let path = log_path(db_path);
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let file = rotate(&path).and_then(|_| fern::log_file(&path));
+    match file {
+        Ok(file) => {
+            let file_level = if verbosity > 1 {
+                log::LevelFilter::Trace
+            } else {
+                log::LevelFilter::Debug
+            };
+            dispatch = dispatch.chain(
+                fern::Dispatch::new()
+                    .format(|out, message, record| {
+                        out.finish(format_args!(
+                            "[{} {} {}] {}",
+                            humantime::format_rfc3339_seconds(SystemTime::now()),
+                            record.level(),
+                            record.target(),
+                            message
+                        ))
+                    })
+                    .level(file_level)
+                    .chain(file),
+            );
+        }
+        Err(err) => eprintln!(
+            "WARNING : couldn't open the log file {} : {}",
+            path.display(),
+            err
+        ),
+    }
+    dispatch
+        .level(log::LevelFilter::Trace)
+        // The GUI libraries log every frame.
+        .level_for("eframe", log::LevelFilter::Warn)
+        .level_for("egui_glow", log::LevelFilter::Warn)
+        .level_for("winit", log::LevelFilter::Warn)
+        .apply()?;
     Ok(())
 }
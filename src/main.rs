@@ -1,13 +1,31 @@
 // vim: nomodeline
 pub mod database;
+pub mod diff;
+pub mod discover;
+pub mod doctor;
+pub mod dotenv;
+pub mod envfile;
+pub mod launch_logs;
+pub mod list;
 pub mod logger;
 pub mod models;
+pub mod output;
+pub mod overrides;
+pub mod platform;
+pub mod providers;
+pub mod registry;
 pub mod schema;
+pub mod search;
+pub mod secrets;
+pub mod store;
+pub mod transfer;
 pub mod ui;
 pub mod vim;
 // use std::thread::spawn;
 
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
 use vim::execute_configuration;
 
 /// Simple program to open an editor
@@ -26,21 +44,398 @@ pub struct Args {
     /// Editor to use
     #[arg(short, long, default_value_t = String::from("neovide"))]
     editor: String,
-    /// force clear env.
+    /// Launch from a clean environment, only keeping HOME, USER, PATH and a few others.
     #[arg(long, default_value_t = false)]
     clear: bool,
+    /// More variables to keep with --clear.
+    #[arg(long, num_args = 1.., requires = "clear")]
+    keep: Vec<String>,
     /// Creation GUI
     #[arg(short, long, default_value_t = false)]
     gui: bool,
+    /// Database file to use, overrides --store and ENVMASTER_DB.
+    #[arg(long, global = true)]
+    db: Option<String>,
+    /// Named store, as declared in the stores file.
+    #[arg(long, global = true)]
+    store: Option<String>,
+    /// Print more details, repeat for more
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Print less, -qq to silence errors too
+    #[arg(short, long, action = clap::ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// List the named stores and the database currently in use
+    Stores {
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// List the stored configurations, groups, variables or executables
+    List {
+        #[arg(value_enum)]
+        kind: list::ListKind,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Export the whole store as a TOML, JSON or YAML document
+    Export {
+        /// Defaults to the output file extension, or TOML
+        #[arg(short, long)]
+        format: Option<transfer::Format>,
+        /// File to write to, prints to stdout otherwise
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Merge an exported document into the store
+    Import {
+        file: PathBuf,
+        /// Defaults to the file extension, or TOML
+        #[arg(short, long)]
+        format: Option<transfer::Format>,
+        /// What to do with entries that exist with different content
+        #[arg(short, long, value_enum, default_value_t)]
+        strategy: transfer::MergeStrategy,
+        /// Only print the changes that would be made
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Keep `cmd:` values as commands to run at launch, instead of importing them as text
+        #[arg(long, default_value_t = false)]
+        allow_commands: bool,
+    },
+    /// Add variables from a .env file or the current environment to a group
+    ImportEnv {
+        /// Group receiving the variables, created when missing
+        group: String,
+        /// .env file to read
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Variables to copy from the current environment, `PREFIX*` selects a whole family
+        #[arg(short = 'e', long, num_args = 1..)]
+        from_env: Vec<String>,
+        /// Store the variables as encrypted secrets
+        #[arg(long, default_value_t = false)]
+        secret: bool,
+        /// Only print which variables would be created or reused
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Keep `cmd:` values as commands to run at launch, instead of importing them as text
+        #[arg(long, default_value_t = false)]
+        allow_commands: bool,
+    },
+    /// Write the variables of a configuration for other tools
+    ExportEnv {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: envfile::EnvFormat,
+        /// File to write to, prints to stdout otherwise
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Leave out the variables marked as secret
+        #[arg(long, default_value_t = false)]
+        no_secrets: bool,
+    },
+    /// Set or remove variables on a single configuration, applied after its groups
+    Override {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        #[arg(short, long)]
+        config: Option<String>,
+        /// NAME=value pairs to set
+        assignments: Vec<String>,
+        /// Names of the overrides to remove
+        #[arg(short, long, num_args = 1..)]
+        remove: Vec<String>,
+        /// Store the values as encrypted secrets
+        #[arg(long, default_value_t = false)]
+        secret: bool,
+        /// Names to remove from the environment at launch
+        #[arg(short, long, num_args = 1..)]
+        unset: Vec<String>,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Show the variables a configuration resolves to, and where each one comes from
+    Resolve {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Compare the variables of two configurations, or of one with the current environment
+    Diff {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        first: Option<String>,
+        /// Configuration to compare it with
+        #[arg(required_unless_present = "current", conflicts_with = "current")]
+        second: Option<String>,
+        /// Compare with the current environment instead, showing what the launch changes
+        #[arg(long, default_value_t = false)]
+        current: bool,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Find variables by name or value, with the groups and configurations using them
+    Find {
+        /// Text to look for, `*` and `?` match like a glob over the whole name or value
+        pattern: String,
+        /// Take the pattern as a regular expression
+        #[arg(short, long, default_value_t = false)]
+        regex: bool,
+        /// Only match names
+        #[arg(long, default_value_t = false, conflicts_with = "values")]
+        names: bool,
+        /// Only match values
+        #[arg(long, default_value_t = false)]
+        values: bool,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// List the executables that can't be launched from this machine
+    Doctor {
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Show the output captured from the detached processes of a configuration
+    Logs {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+        /// Only the output of this process
+        #[arg(short, long)]
+        process: Option<String>,
+        /// Last lines to show, everything otherwise
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+        /// Keep printing what gets written
+        #[arg(short, long, default_value_t = false)]
+        follow: bool,
+        /// List the captured files instead
+        #[arg(short, long, default_value_t = false)]
+        list: bool,
+    },
+    /// List the processes envmaster started that are still running
+    Ps {
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
+    /// Stop the running processes of a configuration
+    Stop {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+    },
+    /// Stop a configuration and launch it again the same way
+    Restart {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+    },
+    /// Run a command with the environment of a configuration, instead of its executable
+    Run {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+        /// Start from a clean environment, only keeping HOME, USER, PATH and a few others
+        #[arg(long, default_value_t = false)]
+        clear: bool,
+        /// More variables to keep with --clear
+        #[arg(long, num_args = 1.., requires = "clear")]
+        keep: Vec<String>,
+        /// The command and its arguments, after `--`
+        #[arg(last = true, required = true)]
+        argv: Vec<String>,
+    },
+    /// Start your shell with the environment of a configuration
+    Shell {
+        /// Configuration id or name, the .envcfg file of the current directory otherwise
+        config: Option<String>,
+        /// Start from a clean environment, only keeping HOME, USER, PATH and a few others
+        #[arg(long, default_value_t = false)]
+        clear: bool,
+        /// More variables to keep with --clear
+        #[arg(long, num_args = 1.., requires = "clear")]
+        keep: Vec<String>,
+        /// Allow starting it from another envmaster shell
+        #[arg(long, default_value_t = false)]
+        nested: bool,
+    },
+    /// Look for installed editors and shells to use as executables
+    Discover {
+        /// Names or commands to add, every new one otherwise
+        names: Vec<String>,
+        /// Store what was found, instead of only listing it
+        #[arg(short, long, default_value_t = false)]
+        add: bool,
+        /// Output format, json is versioned for scripts
+        #[arg(short, long, value_enum, default_value_t)]
+        format: output::OutputFormat,
+    },
 }
 fn main() {
     let args = Args::parse();
+    let db_path = store::resolve_db_path(args.db.as_deref(), args.store.as_deref());
+    let verbosity = (args.verbose as i16 - args.quiet as i16).clamp(-2, 3) as i8;
+    if let Err(err) = logger::setup_logger(verbosity, &db_path) {
+        eprintln!("WARNING : couldn't set up logging : {}", err);
+    }
+    if let Some(command) = &args.command {
+        match command {
+            Commands::Stores { format } => store::list_stores(&db_path, *format),
+            Commands::List { kind, format } => list::list_command(&db_path, *kind, *format),
+            Commands::Export { format, output } => {
+                transfer::export_command(&db_path, *format, output.as_deref())
+            }
+            Commands::Import {
+                file,
+                format,
+                strategy,
+                dry_run,
+                allow_commands,
+            } => transfer::import_command(
+                &db_path,
+                file,
+                *format,
+                *strategy,
+                *dry_run,
+                *allow_commands,
+            ),
+            Commands::ImportEnv {
+                group,
+                file,
+                from_env,
+                secret,
+                dry_run,
+                allow_commands,
+            } => transfer::import_variables_command(
+                &db_path,
+                group,
+                file.as_deref(),
+                from_env,
+                *secret,
+                *dry_run,
+                *allow_commands,
+            ),
+            Commands::ExportEnv {
+                config,
+                format,
+                output,
+                no_secrets,
+            } => envfile::export_command(
+                &db_path,
+                config.as_deref(),
+                *format,
+                output.as_deref(),
+                !*no_secrets,
+            ),
+            Commands::Override {
+                config,
+                assignments,
+                remove,
+                secret,
+                unset,
+                format,
+            } => overrides::override_command(
+                &db_path,
+                config.as_deref(),
+                assignments,
+                unset,
+                remove,
+                *secret,
+                *format,
+            ),
+            Commands::Resolve { config, format } => {
+                vim::resolve_command(&db_path, config.as_deref(), *format)
+            }
+            Commands::Diff {
+                first,
+                second,
+                current,
+                format,
+            } => diff::diff_command(
+                &db_path,
+                first.as_deref(),
+                second.as_deref(),
+                *current,
+                *format,
+            ),
+            Commands::Find {
+                pattern,
+                regex,
+                names,
+                values,
+                format,
+            } => search::find_command(&db_path, pattern, *regex, *names, *values, *format),
+            Commands::Doctor { format } => doctor::doctor_command(&db_path, *format),
+            Commands::Ps { format } => registry::ps_command(&db_path, *format),
+            Commands::Stop { config } => registry::stop_command(&db_path, config.as_deref()),
+            Commands::Restart { config } => registry::restart_command(&db_path, config.as_deref()),
+            Commands::Logs {
+                config,
+                process,
+                lines,
+                follow,
+                list,
+            } => launch_logs::logs_command(
+                &db_path,
+                config.as_deref(),
+                process.as_deref(),
+                *lines,
+                *follow,
+                *list,
+            ),
+            Commands::Run {
+                config,
+                clear,
+                keep,
+                argv,
+            } => vim::run_command(
+                &db_path,
+                config.as_deref(),
+                argv,
+                vim::LaunchOptions {
+                    path: None,
+                    clear: *clear,
+                    keep: keep.clone(),
+                },
+            ),
+            Commands::Shell {
+                config,
+                clear,
+                keep,
+                nested,
+            } => vim::subshell_command(
+                &db_path,
+                config.as_deref(),
+                *nested,
+                vim::LaunchOptions {
+                    path: None,
+                    clear: *clear,
+                    keep: keep.clone(),
+                },
+            ),
+            Commands::Discover { names, add, format } => {
+                discover::discover_command(&db_path, names, *add, *format)
+            }
+        }
+        return;
+    }
     if args.gui {
-        ui::show().unwrap();
+        ui::show(db_path).unwrap();
         return;
     }
     // let handle = spawn(|| {
-    execute_configuration(args);
+    execute_configuration(args, &db_path);
     // });
     // if handle.join().is_err() {
     // println!("An error occured recovering the thread...");
@@ -1,38 +1,339 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::schema;
+use crate::{platform, schema, secrets::display_value};
 use diesel::prelude::*;
-#[derive(Queryable, Identifiable, Selectable, Associations, PartialEq, Clone, Debug)]
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+#[derive(Queryable, Identifiable, Selectable, Associations, PartialEq, Clone, Debug, Serialize)]
 #[diesel(table_name = schema::configurations)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(belongs_to(Executable, foreign_key = exec))]
 pub struct Configuration {
     pub id: i32,
     pub name: String,
-    pub exec: i32,
+    /// Empty when the executable is inherited from the parent.
+    pub exec: Option<i32>,
+    /// Configuration this one extends.
+    pub parent: Option<i32>,
+    /// One of [`SINGLE_INSTANCE_POLICIES`], empty when it may run several times.
+    pub single_instance: Option<String>,
 }
 
+pub const SINGLE_INSTANCE_REFUSE: &str = "refuse";
+pub const SINGLE_INSTANCE_NOTIFY: &str = "notify";
+pub const SINGLE_INSTANCE_REPLACE: &str = "replace";
+/// What launching a configuration that is already running does : `refuse` fails, `notify` says
+/// where it runs and starts nothing, `replace` stops the running instance first.
+pub const SINGLE_INSTANCE_POLICIES: &[&str] = &[
+    SINGLE_INSTANCE_REFUSE,
+    SINGLE_INSTANCE_NOTIFY,
+    SINGLE_INSTANCE_REPLACE,
+];
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct LinkedConfiguration {
     pub configuration: Configuration,
     pub groups: Vec<LinkedGroups>,
+    /// Parents of this configuration, closest first. Ancestors don't carry their own ancestors.
+    pub ancestors: Vec<LinkedConfiguration>,
+    /// Variables set on this configuration only, applied after its groups. Their ids point into
+    /// `configuration_variables`.
+    pub variables: Vec<Environment>,
+    /// Processes started alongside the executable, in order.
+    pub processes: Vec<Process>,
+    /// Commands run before the launch and after the exit, in order.
+    pub hooks: Vec<Hook>,
+}
+
+/// Source shown for variables set on the configuration itself.
+pub const OVERRIDE_SOURCE: &str = "override";
+
+/// Name and value pattern of a search. Plain text matches anywhere, `*` and `?` make it a glob
+/// over the whole text, both ignoring case. Regular expressions are taken as written.
+#[derive(Clone, Debug)]
+pub struct SearchPattern {
+    regex: regex::Regex,
+    /// Match variable, group and configuration names.
+    pub names: bool,
+    /// Match variable values. Secret values are encrypted, so they never match.
+    pub values: bool,
+}
+
+impl SearchPattern {
+    pub fn new(pattern: &str, is_regex: bool) -> Result<Self, regex::Error> {
+        let source = if is_regex {
+            pattern.to_owned()
+        } else if pattern.contains(['*', '?']) {
+            let mut source = String::from("(?i)^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            source.push('$');
+            source
+        } else {
+            format!("(?i){}", regex::escape(pattern))
+        };
+        Ok(SearchPattern {
+            regex: regex::Regex::new(&source)?,
+            names: true,
+            values: true,
+        })
+    }
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.names && self.regex.is_match(name)
+    }
+    pub fn matches(&self, env: &Environment) -> bool {
+        self.matches_name(&env.name)
+            || (self.values && !env.secret && !env.unset && self.regex.is_match(&env.value))
+    }
+}
+
+/// A configuration affected by a variable, group or executable.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct ConfigurationUse {
+    pub configuration_id: i32,
+    pub configuration: String,
+    /// As in [`ResolvedVariable`], or `executable` and `process`, followed by `via parent` when
+    /// inherited.
+    pub source: String,
+}
+
+/// Where a variable, group or executable is used.
+#[derive(PartialEq, Clone, Debug, Default, Serialize)]
+pub struct Usage {
+    /// Groups holding it, `group > included` when it comes through an include.
+    pub groups: Vec<String>,
+    pub configurations: Vec<ConfigurationUse>,
+}
+
+impl Usage {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.configurations.is_empty()
+    }
+    /// Sorts by name and drops the duplicates.
+    pub fn sort(&mut self) {
+        self.groups.sort();
+        self.groups.dedup();
+        self.configurations
+            .sort_by(|a, b| (&a.configuration, &a.source).cmp(&(&b.configuration, &b.source)));
+        self.configurations.dedup();
+    }
+}
+
+/// Where a found variable is stored, the table its `environment.id` belongs to.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableKind {
+    /// A row of `environments`, shared through groups.
+    Variable,
+    /// A row of `configuration_variables`, set on a single configuration.
+    Override,
+}
+
+/// A variable found by a search. Configuration overrides have no groups.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct VariableUsage {
+    pub kind: VariableKind,
+    /// Configuration setting the override, `None` for variables.
+    pub override_of: Option<i32>,
+    pub environment: Environment,
+    pub usage: Usage,
+}
+
+#[derive(PartialEq, Clone, Debug, Default, Serialize)]
+pub struct SearchResults {
+    pub variables: Vec<VariableUsage>,
+    /// Groups whose name matches.
+    pub groups: Vec<GroupedEnvironment>,
+    /// Configurations whose name matches.
+    pub configurations: Vec<Configuration>,
+}
+
+impl SearchResults {
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty() && self.groups.is_empty() && self.configurations.is_empty()
+    }
+    /// Configurations matching by name or using one of the variables found.
+    pub fn configuration_ids(&self) -> HashSet<i32> {
+        self.configurations
+            .iter()
+            .map(|cfg| cfg.id)
+            .chain(
+                self.variables
+                    .iter()
+                    .flat_map(|var| var.usage.configurations.iter())
+                    .map(|u| u.configuration_id),
+            )
+            .collect()
+    }
+}
+
+/// A variable as it ends up after inheritance, with where it comes from.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct ResolvedVariable {
+    pub environment: Environment,
+    pub configuration: String,
+    /// Group the variable comes from, `group > included` for included groups, or
+    /// [`OVERRIDE_SOURCE`].
+    pub source: String,
+    /// Values with the same name set earlier in the chain, hidden by this one.
+    pub overrides: Vec<Environment>,
 }
+
 impl LinkedConfiguration {
-    pub fn get_environments(&self) -> HashMap<String, String> {
-        let envs: HashMap<String, String> = self
-            .groups
-            .clone()
-            .into_iter()
-            .flat_map(|x| x.environments)
-            .map(|y| (y.name, y.value))
+    /// Groups from the furthest ancestor down to this configuration, each with the
+    /// configuration it was linked to. Later groups override earlier ones.
+    pub fn effective_groups(&self) -> Vec<(&Configuration, &LinkedGroups)> {
+        let mut groups = vec![];
+        for cfg in self.ancestors.iter().rev().chain(std::iter::once(self)) {
+            for group in cfg.groups.iter() {
+                groups.push((&cfg.configuration, group));
+            }
+        }
+        groups
+    }
+    /// Own executable, or the closest ancestor's.
+    pub fn executable(&self) -> Option<i32> {
+        std::iter::once(self)
+            .chain(self.ancestors.iter())
+            .find_map(|cfg| cfg.configuration.exec)
+    }
+    /// The single instance policy : its own, or the closest ancestor's.
+    pub fn single_instance(&self) -> Option<&str> {
+        std::iter::once(self)
+            .chain(self.ancestors.iter())
+            .find_map(|cfg| cfg.configuration.single_instance.as_deref())
+    }
+    /// The processes this configuration starts : its own, or the closest ancestor's when it
+    /// has none.
+    pub fn processes(&self) -> &[Process] {
+        std::iter::once(self)
+            .chain(self.ancestors.iter())
+            .map(|cfg| cfg.processes.as_slice())
+            .find(|processes| !processes.is_empty())
+            .unwrap_or_default()
+    }
+    /// Hooks of `stage` in the order they run. Pre-launch hooks of the furthest ancestor run
+    /// first, post-exit hooks of this configuration do, so parents wrap their children.
+    pub fn hooks(&self, stage: &str) -> Vec<&Hook> {
+        let mut chain: Vec<&LinkedConfiguration> = self
+            .ancestors
+            .iter()
+            .rev()
+            .chain(std::iter::once(self))
             .collect();
+        if stage == HOOK_POST {
+            chain.reverse();
+        }
+        chain
+            .into_iter()
+            .flat_map(|cfg| cfg.hooks.iter())
+            .filter(|hook| hook.stage == stage)
+            .collect()
+    }
+    /// Every variable in the order it applies, from the furthest ancestor down to this
+    /// configuration : each configuration's groups, then its own variables. Comes with the
+    /// configuration and the source setting it, as in [`ResolvedVariable`].
+    pub fn effective_environments(&self) -> Vec<(&Configuration, String, &Environment)> {
+        let mut envs = vec![];
+        for cfg in self.ancestors.iter().rev().chain(std::iter::once(self)) {
+            for linked in cfg.groups.iter() {
+                if !platform::applies(linked.group.condition.as_deref()) {
+                    continue;
+                }
+                for (group, env) in linked.expanded_environments() {
+                    let source = if group.id == linked.group.id {
+                        group.name.clone()
+                    } else {
+                        format!("{} > {}", linked.group.name, group.name)
+                    };
+                    envs.push((&cfg.configuration, source, env));
+                }
+            }
+            for env in cfg.variables.iter() {
+                envs.push((&cfg.configuration, OVERRIDE_SOURCE.to_owned(), env));
+            }
+        }
         envs
     }
+    pub fn get_environments(&self) -> HashMap<String, String> {
+        let mut envs: HashMap<String, String> = HashMap::new();
+        for (_, _, env) in self.effective_environments() {
+            if env.unset {
+                envs.remove(&env.name);
+            } else {
+                envs.insert(env.name.clone(), env.value.clone());
+            }
+        }
+        envs
+    }
+    /// Names to remove from the environment the configuration is launched from : unset last
+    /// along the chain, and not set again afterwards.
+    pub fn unset_variables(&self) -> Vec<String> {
+        let mut unset: Vec<String> = vec![];
+        for (_, _, env) in self.effective_environments() {
+            unset.retain(|name| *name != env.name);
+            if env.unset {
+                unset.push(env.name.clone());
+            }
+        }
+        unset
+    }
+    /// Resolved variables sorted by name, with the configuration and source they come from.
+    pub fn resolve_sources(&self) -> Vec<ResolvedVariable> {
+        let mut resolved: Vec<ResolvedVariable> = vec![];
+        for (cfg, source, env) in self.effective_environments() {
+            let source = ResolvedVariable {
+                environment: env.clone(),
+                configuration: cfg.name.clone(),
+                source,
+                overrides: vec![],
+            };
+            match resolved.iter_mut().find(|r| r.environment.name == env.name) {
+                Some(previous) => {
+                    let mut overrides = std::mem::take(&mut previous.overrides);
+                    overrides.push(previous.environment.clone());
+                    *previous = ResolvedVariable {
+                        overrides,
+                        ..source
+                    };
+                }
+                None => resolved.push(source),
+            }
+        }
+        resolved.sort_by(|a, b| a.environment.name.cmp(&b.environment.name));
+        resolved
+    }
 }
 #[derive(PartialEq, Clone, Debug)]
 pub struct LinkedGroups {
     pub group: GroupedEnvironment,
     pub environments: Vec<Environment>,
+    /// Included groups in order, themselves expanded.
+    pub includes: Vec<LinkedGroups>,
+}
+impl LinkedGroups {
+    /// Variables of the included groups in order, then the group's own, each with the group
+    /// defining it. Later variables override earlier ones with the same name. Variables and
+    /// includes whose condition doesn't hold on this platform are left out.
+    pub fn expanded_environments(&self) -> Vec<(&GroupedEnvironment, &Environment)> {
+        let mut envs = vec![];
+        for include in self.includes.iter() {
+            if platform::applies(include.group.condition.as_deref()) {
+                envs.extend(include.expanded_environments());
+            }
+        }
+        envs.extend(
+            self.environments
+                .iter()
+                .filter(|env| platform::applies(env.condition.as_deref()))
+                .map(|env| (&self.group, env)),
+        );
+        envs
+    }
 }
 #[derive(Insertable, Clone, Copy, Debug)]
 #[diesel(table_name = schema::m_to_m_group_configs)]
@@ -56,7 +357,9 @@ pub struct GroupEnvLinkInsert<'a> {
     pub group_id: &'a i32,
     pub env_id: &'a i32,
 }
-#[derive(Queryable, Identifiable, Selectable, PartialEq, Clone, Debug)]
+/// Launch modes : `wait` keeps envmaster running until the process exits, `detach` leaves it.
+pub const MODES: &[&str] = &["wait", "detach"];
+#[derive(Queryable, Identifiable, Selectable, PartialEq, Clone, Debug, Serialize)]
 #[diesel(table_name = schema::executables)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Executable {
@@ -64,14 +367,20 @@ pub struct Executable {
     pub name: String,
     pub executable: String,
     pub mode: String,
+    /// Platform condition, see [`crate::platform`]. Executables sharing a name are variants of
+    /// the same program, the one matching the platform is launched.
+    pub condition: Option<String>,
 }
 
-#[derive(Queryable, Identifiable, Selectable, PartialEq, Clone, Debug)]
+#[derive(Queryable, Identifiable, Selectable, PartialEq, Clone, Debug, Serialize)]
 #[diesel(table_name = schema::group_environments)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct GroupedEnvironment {
     pub id: i32,
     pub name: String,
+    /// Platform condition, see [`crate::platform`]. The group and its includes are skipped
+    /// where it doesn't hold.
+    pub condition: Option<String>,
 }
 
 #[derive(Queryable, Identifiable, Selectable, Eq, Hash, PartialEq, Debug, Clone)]
@@ -80,7 +389,53 @@ pub struct GroupedEnvironment {
 pub struct Environment {
     pub id: i32,
     pub name: String,
+    /// Encrypted when `secret` is set, see [`crate::secrets`].
     pub value: String,
+    pub secret: bool,
+    /// Removes the variable from the environment instead of setting it, the value is unused.
+    pub unset: bool,
+    /// Platform condition, see [`crate::platform`].
+    pub condition: Option<String>,
+}
+
+/// Secret values are masked and unset ones have none, so the ciphertext never gets printed.
+impl Serialize for Environment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Environment", 6)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("value", &(!self.unset).then(|| display_value(self)))?;
+        state.serialize_field("secret", &self.secret)?;
+        state.serialize_field("unset", &self.unset)?;
+        state.serialize_field("condition", &self.condition)?;
+        state.end()
+    }
+}
+
+/// A variable set directly on a configuration, see [`LinkedConfiguration::variables`].
+#[derive(Queryable, Identifiable, Associations, Selectable, PartialEq, Clone, Debug)]
+#[diesel(table_name = schema::configuration_variables)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(Configuration, foreign_key = config_id))]
+pub struct ConfigurationVariable {
+    pub id: i32,
+    pub config_id: i32,
+    pub name: String,
+    pub value: String,
+    pub secret: bool,
+    pub unset: bool,
+}
+impl From<ConfigurationVariable> for Environment {
+    fn from(var: ConfigurationVariable) -> Self {
+        Environment {
+            id: var.id,
+            name: var.name,
+            value: var.value,
+            secret: var.secret,
+            unset: var.unset,
+            condition: None,
+        }
+    }
 }
 
 #[derive(Queryable, Identifiable, Associations, Selectable, PartialEq, Clone)]
@@ -105,6 +460,145 @@ pub struct GroupEnvsLink {
     pub env_id: i32,
 }
 
+#[derive(Queryable, Identifiable, Selectable, Associations, PartialEq, Clone, Debug)]
+#[diesel(table_name = schema::m_to_m_group_includes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(GroupedEnvironment, foreign_key = group_id))]
+pub struct GroupInclude {
+    pub id: i32,
+    pub group_id: i32,
+    pub included_id: i32,
+    pub position: i32,
+}
+#[derive(Insertable, Clone, Copy)]
+#[diesel(table_name = schema::m_to_m_group_includes)]
+pub struct GroupIncludeInsert<'a> {
+    pub group_id: &'a i32,
+    pub included_id: &'a i32,
+    pub position: i32,
+}
+
+/// A process a configuration starts with the same environment as its executable.
+#[derive(Queryable, Identifiable, Selectable, Associations, PartialEq, Clone, Debug)]
+#[diesel(table_name = schema::configuration_processes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(Configuration, foreign_key = config_id))]
+pub struct Process {
+    pub id: i32,
+    pub config_id: i32,
+    pub exec_id: i32,
+    pub position: i32,
+    /// Command line arguments, split like a shell would.
+    pub args: String,
+    /// Directory to start in, the current one otherwise.
+    pub cwd: Option<String>,
+    /// `wait` or `detach`, the executable's mode otherwise.
+    pub mode: Option<String>,
+    /// Stop the other waited processes when this one exits.
+    pub stop_others: bool,
+}
+#[derive(Insertable, Clone)]
+#[diesel(table_name = schema::configuration_processes)]
+pub struct ProcessInsert {
+    pub config_id: i32,
+    pub exec_id: i32,
+    pub position: i32,
+    pub args: String,
+    pub cwd: Option<String>,
+    pub mode: Option<String>,
+    pub stop_others: bool,
+}
+
+pub const HOOK_PRE: &str = "pre";
+pub const HOOK_POST: &str = "post";
+pub const HOOK_STAGES: &[&str] = &[HOOK_PRE, HOOK_POST];
+/// What a failing hook does : `abort` stops the launch, `warn` prints a warning, `ignore`
+/// carries on silently.
+pub const FAILURE_POLICIES: &[&str] = &["abort", "warn", "ignore"];
+
+/// A shell command run with the configuration's environment, before the launch or after the
+/// exit depending on `stage`.
+#[derive(Queryable, Identifiable, Selectable, Associations, PartialEq, Clone, Debug)]
+#[diesel(table_name = schema::configuration_hooks)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(Configuration, foreign_key = config_id))]
+pub struct Hook {
+    pub id: i32,
+    pub config_id: i32,
+    pub position: i32,
+    /// [`HOOK_PRE`] or [`HOOK_POST`].
+    pub stage: String,
+    pub command: String,
+    /// One of [`FAILURE_POLICIES`].
+    pub on_failure: String,
+}
+#[derive(Insertable, Clone)]
+#[diesel(table_name = schema::configuration_hooks)]
+pub struct HookInsert {
+    pub config_id: i32,
+    pub position: i32,
+    pub stage: String,
+    pub command: String,
+    pub on_failure: String,
+}
+impl From<&Hook> for HookInsert {
+    fn from(hook: &Hook) -> Self {
+        HookInsert {
+            config_id: hook.config_id,
+            position: hook.position,
+            stage: hook.stage.clone(),
+            command: hook.command.clone(),
+            on_failure: hook.on_failure.clone(),
+        }
+    }
+}
+
+impl From<&Process> for ProcessInsert {
+    fn from(process: &Process) -> Self {
+        ProcessInsert {
+            config_id: process.config_id,
+            exec_id: process.exec_id,
+            position: process.position,
+            args: process.args.clone(),
+            cwd: process.cwd.clone(),
+            mode: process.mode.clone(),
+            stop_others: process.stop_others,
+        }
+    }
+}
+
+/// A process envmaster started, kept until it's found dead. Comes with the launch options so
+/// the configuration can be restarted the same way.
+#[derive(Queryable, Identifiable, Selectable, PartialEq, Clone, Debug, Serialize)]
+#[diesel(table_name = schema::running_processes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RunningProcess {
+    pub id: i32,
+    pub config_id: i32,
+    pub name: String,
+    pub pid: i32,
+    /// RFC 3339 time of the launch.
+    pub started_at: String,
+    pub path: Option<String>,
+    pub clear: bool,
+    /// Variables kept with `clear`, space separated.
+    pub keep: String,
+    /// Start time reported by the system, see [`crate::registry::is_running`].
+    pub identity: String,
+}
+#[derive(Insertable, Clone)]
+#[diesel(table_name = schema::running_processes)]
+pub struct RunningProcessInsert {
+    pub config_id: i32,
+    pub name: String,
+    pub pid: i32,
+    pub started_at: String,
+    pub path: Option<String>,
+    pub clear: bool,
+    pub keep: String,
+    pub identity: String,
+}
+
 pub trait DbObject {
     fn id(&self) -> i32;
 }
@@ -0,0 +1,57 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Version of the JSON documents, raised when a field changes meaning or goes away. New fields
+/// can appear without it changing.
+pub const JSON_VERSION: u32 = 1;
+
+/// How list and inspect commands print their results.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum OutputFormat {
+    /// For people, the layout may change.
+    #[default]
+    Text,
+    /// Aligned columns with a header row.
+    Table,
+    /// A versioned document : `{"version": 1, "kind": "...", "data": ...}`.
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonDocument<'a, T: Serialize + ?Sized> {
+    version: u32,
+    kind: &'a str,
+    data: &'a T,
+}
+
+/// Prints `data` as a [`JSON_VERSION`] document, `kind` naming what it holds.
+pub fn print_json<T: Serialize + ?Sized>(kind: &str, data: &T) {
+    let document = JsonDocument {
+        version: JSON_VERSION,
+        kind,
+        data,
+    };
+    println!("{}", serde_json::to_string_pretty(&document).unwrap());
+}
+
+/// Prints `rows` under `headers`, each column as wide as its widest cell.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
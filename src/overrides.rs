@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use crate::{
+    database::{
+        delete_configuration_variable, establish_connection, get_configuration_variables,
+        set_configuration_variable,
+    },
+    output::{print_json, print_table, OutputFormat},
+    secrets::{display_assignment, display_value, encrypt},
+    vim::{config_target, resolve_configuration},
+};
+
+/// Sets and removes the variables of one configuration, then lists the ones it holds.
+pub fn override_command(
+    db_path: &Path,
+    target: Option<&str>,
+    assignments: &[String],
+    unset: &[String],
+    remove: &[String],
+    secret: bool,
+    format: OutputFormat,
+) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    let config_id = config.configuration.id;
+    for assignment in assignments {
+        let Some((name, value)) = assignment.split_once('=') else {
+            panic!("Expected NAME=value, got {}.", assignment);
+        };
+        let value = if secret {
+            encrypt(value).unwrap_or_else(|err| panic!("Couldn't encrypt {} : {}", name, err))
+        } else {
+            value.to_owned()
+        };
+        set_configuration_variable(&mut conn, config_id, name, &value, secret, false).unwrap();
+    }
+    for name in unset {
+        set_configuration_variable(&mut conn, config_id, name, "", false, true).unwrap();
+    }
+    for name in remove {
+        if delete_configuration_variable(&mut conn, config_id, name).unwrap() == 0 {
+            log::warn!("{} doesn't override {}.", config.configuration.name, name);
+        }
+    }
+    let vars = get_configuration_variables(&mut conn, config_id).unwrap();
+    match format {
+        OutputFormat::Json => print_json("overrides", &vars),
+        OutputFormat::Table => print_table(
+            &["NAME", "VALUE"],
+            &vars
+                .iter()
+                .map(|var| vec![var.name.clone(), display_value(var).to_owned()])
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::Text => {
+            if vars.is_empty() {
+                println!(
+                    "{} doesn't override any variable.",
+                    config.configuration.name
+                );
+            }
+            for var in vars.iter() {
+                println!("{}", display_assignment(var));
+            }
+        }
+    }
+}
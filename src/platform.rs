@@ -0,0 +1,269 @@
+use std::{process::Command, sync::OnceLock};
+
+use log::warn;
+
+use crate::models::Executable;
+
+/// What conditions are evaluated against.
+#[derive(Debug, Clone)]
+pub struct Platform {
+    /// `windows`, `linux`, `macos`...
+    pub os: String,
+    /// `unix` or `windows`.
+    pub family: String,
+    /// `x86_64`, `aarch64`...
+    pub arch: String,
+    pub host: String,
+}
+
+impl Platform {
+    pub fn current() -> &'static Platform {
+        static CURRENT: OnceLock<Platform> = OnceLock::new();
+        CURRENT.get_or_init(|| Platform {
+            os: std::env::consts::OS.to_owned(),
+            family: std::env::consts::FAMILY.to_owned(),
+            arch: std::env::consts::ARCH.to_owned(),
+            host: hostname().unwrap_or_default(),
+        })
+    }
+    fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "os" => Some(&self.os),
+            "family" => Some(&self.family),
+            "arch" => Some(&self.arch),
+            "host" | "hostname" => Some(&self.host),
+            _ => None,
+        }
+    }
+}
+
+fn hostname() -> Option<String> {
+    let from_env = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok();
+    let from_file = || {
+        std::fs::read_to_string("/etc/hostname")
+            .or_else(|_| std::fs::read_to_string("/proc/sys/kernel/hostname"))
+            .ok()
+    };
+    let from_command = || {
+        Command::new("hostname")
+            .output()
+            .ok()
+            .and_then(|out| String::from_utf8(out.stdout).ok())
+    };
+    from_env
+        .or_else(from_file)
+        .or_else(from_command)
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    key: String,
+    negated: bool,
+    values: Vec<String>,
+}
+
+/// Parses a condition such as `os=linux|macos, arch!=aarch64, host=work-laptop`. Every term
+/// must hold, `|` separates accepted values, and keys are `os`, `family`, `arch` and `host`.
+fn parse(condition: &str) -> Result<Vec<Term>, String> {
+    let mut terms = vec![];
+    for term in condition
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        let (key, negated, values) = if let Some((key, values)) = term.split_once("!=") {
+            (key, true, values)
+        } else if let Some((key, values)) = term.split_once('=') {
+            (key, false, values)
+        } else {
+            return Err(format!("`{}` is not a `key=value` term", term));
+        };
+        let key = key.trim().to_lowercase();
+        if Platform::current().get(&key).is_none() {
+            return Err(format!(
+                "unknown key `{}`, expected os, family, arch or host",
+                key
+            ));
+        }
+        let values: Vec<String> = values
+            .split('|')
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            return Err(format!("`{}` has no value", term));
+        }
+        terms.push(Term {
+            key,
+            negated,
+            values,
+        });
+    }
+    Ok(terms)
+}
+
+/// Checks a condition before it is stored. Empty conditions are valid and always hold.
+pub fn validate(condition: &str) -> Result<(), String> {
+    parse(condition).map(|_| ())
+}
+
+pub fn matches(condition: &str, platform: &Platform) -> Result<bool, String> {
+    Ok(parse(condition)?.iter().all(|term| {
+        let current = platform.get(&term.key).unwrap_or_default().to_lowercase();
+        term.values.contains(&current) != term.negated
+    }))
+}
+
+/// Whether something carrying `condition` applies on this machine. A condition that doesn't
+/// parse never applies, with a warning, so a typo can't leak a value onto the wrong platform.
+pub fn applies(condition: Option<&str>) -> bool {
+    applies_on(condition, Platform::current())
+}
+
+fn applies_on(condition: Option<&str>, platform: &Platform) -> bool {
+    let Some(condition) = condition else {
+        return true;
+    };
+    match matches(condition, platform) {
+        Ok(applies) => applies,
+        Err(err) => {
+            warn!("ignoring the condition `{}` : {}", condition, err);
+            false
+        }
+    }
+}
+
+/// The executable to launch for `chosen` on this machine : itself when its condition holds,
+/// otherwise the first executable sharing its name whose condition does. Executables with the
+/// same name are variants of one program for different platforms.
+pub fn select_executable<'a>(
+    chosen: &'a Executable,
+    all: &'a [Executable],
+) -> Option<&'a Executable> {
+    select_executable_on(chosen, all, Platform::current())
+}
+
+fn select_executable_on<'a>(
+    chosen: &'a Executable,
+    all: &'a [Executable],
+    platform: &Platform,
+) -> Option<&'a Executable> {
+    if applies_on(chosen.condition.as_deref(), platform) {
+        return Some(chosen);
+    }
+    all.iter().find(|e| {
+        e.name == chosen.name && e.id != chosen.id && applies_on(e.condition.as_deref(), platform)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(os: &str, arch: &str, host: &str) -> Platform {
+        Platform {
+            os: os.to_owned(),
+            family: if os == "windows" { "windows" } else { "unix" }.to_owned(),
+            arch: arch.to_owned(),
+            host: host.to_owned(),
+        }
+    }
+
+    fn executable(id: i32, name: &str, condition: Option<&str>) -> Executable {
+        Executable {
+            id,
+            name: name.to_owned(),
+            executable: format!("{}-{}", name, id),
+            mode: "wait".to_owned(),
+            condition: condition.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parses_terms() {
+        let terms = parse(" os=Linux|macos , arch!=aarch64,, host = Work-Laptop ").unwrap();
+        assert_eq!(
+            terms,
+            [
+                Term {
+                    key: "os".to_owned(),
+                    negated: false,
+                    values: vec!["linux".to_owned(), "macos".to_owned()],
+                },
+                Term {
+                    key: "arch".to_owned(),
+                    negated: true,
+                    values: vec!["aarch64".to_owned()],
+                },
+                Term {
+                    key: "host".to_owned(),
+                    negated: false,
+                    values: vec!["work-laptop".to_owned()],
+                },
+            ]
+        );
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        assert!(validate("linux").is_err());
+        assert!(validate("distro=arch").is_err());
+        assert!(validate("os=").is_err());
+        assert!(validate("os=|").is_err());
+        assert!(validate("hostname=box").is_ok());
+    }
+
+    #[test]
+    fn every_term_must_hold() {
+        let laptop = platform("linux", "x86_64", "work-laptop");
+        assert!(matches("os=linux|macos", &laptop).unwrap());
+        assert!(matches("os=linux, host=work-laptop", &laptop).unwrap());
+        assert!(!matches("os=linux, host=desktop", &laptop).unwrap());
+        assert!(matches("arch!=aarch64", &laptop).unwrap());
+        assert!(!matches("os!=linux|windows", &laptop).unwrap());
+        assert!(matches("family=unix", &laptop).unwrap());
+        assert!(matches("OS=LINUX", &laptop).unwrap());
+    }
+
+    #[test]
+    fn unparsable_conditions_never_apply() {
+        let laptop = platform("linux", "x86_64", "work-laptop");
+        assert!(applies_on(None, &laptop));
+        assert!(applies_on(Some(""), &laptop));
+        assert!(!applies_on(Some("os linux"), &laptop));
+    }
+
+    #[test]
+    fn chosen_executable_comes_first() {
+        let all = [
+            executable(1, "vim", Some("os=linux")),
+            executable(2, "vim", None),
+        ];
+        let linux = platform("linux", "x86_64", "box");
+        assert_eq!(select_executable_on(&all[1], &all, &linux).unwrap().id, 2);
+        assert_eq!(select_executable_on(&all[0], &all, &linux).unwrap().id, 1);
+    }
+
+    #[test]
+    fn falls_back_on_a_variant() {
+        let all = [
+            executable(1, "vim", Some("os=macos")),
+            executable(2, "nano", Some("os=windows")),
+            executable(3, "vim", Some("os=windows")),
+            executable(4, "vim", Some("os=linux")),
+        ];
+        let windows = platform("windows", "x86_64", "box");
+        assert_eq!(select_executable_on(&all[0], &all, &windows).unwrap().id, 3);
+        let linux = platform("linux", "x86_64", "box");
+        assert_eq!(select_executable_on(&all[0], &all, &linux).unwrap().id, 4);
+        let bsd = platform("freebsd", "x86_64", "box");
+        assert!(select_executable_on(&all[0], &all, &bsd).is_none());
+        // Variants are matched by name, nano isn't one of vim's.
+        assert!(select_executable_on(&all[1], &all, &linux).is_none());
+    }
+}
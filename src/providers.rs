@@ -0,0 +1,324 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::Read,
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::Duration,
+};
+
+use wait_timeout::ChildExt;
+
+/// Seconds a provider may take before the launch is aborted, overriding [`DEFAULT_TIMEOUT`].
+pub const TIMEOUT_ENV_VAR: &str = "ENVMASTER_PROVIDER_TIMEOUT";
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Values starting with this prefix are used as is, even if they look like a reference.
+pub const RAW_PREFIX: &str = "raw:";
+/// Scheme of [`CommandProvider`].
+pub const COMMAND_SCHEME: &str = "cmd";
+
+/// `value` kept as text when it would run a command at launch, for values the user didn't
+/// write : imported documents and `.env` files. `None` when there's nothing to escape.
+pub fn escape_command(value: &str) -> Option<String> {
+    value
+        .strip_prefix(COMMAND_SCHEME)
+        .is_some_and(|rest| rest.starts_with(':'))
+        .then(|| format!("{}{}", RAW_PREFIX, value))
+}
+
+/// Resolves references of the form `scheme:argument` into the value to launch with.
+pub trait SecretProvider {
+    fn scheme(&self) -> &'static str;
+    fn resolve(&self, argument: &str, timeout: Duration) -> Result<String, String>;
+}
+
+/// `file:/run/secrets/token` : the content of a file, without its trailing newline.
+pub struct FileProvider;
+impl SecretProvider for FileProvider {
+    fn scheme(&self) -> &'static str {
+        "file"
+    }
+    fn resolve(&self, argument: &str, timeout: Duration) -> Result<String, String> {
+        let path = argument.to_owned();
+        let (sender, receiver) = mpsc::channel();
+        // Reading a fifo or a network mount can hang, so the read happens on its own thread.
+        std::thread::spawn(move || {
+            let _ = sender.send(std::fs::read_to_string(&path));
+        });
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(content)) => Ok(trim_newline(content)),
+            Ok(Err(err)) => Err(format!("couldn't read {} : {}", argument, err)),
+            Err(_) => Err(format!("reading {} timed out", argument)),
+        }
+    }
+}
+
+/// `cmd:pass show db/prod` : the standard output of a command, split like a shell would but
+/// run without one.
+pub struct CommandProvider;
+impl SecretProvider for CommandProvider {
+    fn scheme(&self) -> &'static str {
+        COMMAND_SCHEME
+    }
+    fn resolve(&self, argument: &str, timeout: Duration) -> Result<String, String> {
+        let words = shlex::split(argument).ok_or_else(|| "unbalanced quotes".to_string())?;
+        let Some((program, args)) = words.split_first() else {
+            return Err("empty command".into());
+        };
+        let mut command = Command::new(program);
+        command.args(args);
+        run_with_timeout(command, timeout)
+    }
+}
+
+/// `keyring:service/account` : a password from the system keyring, through `secret-tool` on
+/// Linux and `security` on macOS.
+pub struct KeyringProvider;
+impl SecretProvider for KeyringProvider {
+    fn scheme(&self) -> &'static str {
+        "keyring"
+    }
+    fn resolve(&self, argument: &str, timeout: Duration) -> Result<String, String> {
+        let Some((service, account)) = argument.split_once('/') else {
+            return Err("expected keyring:service/account".into());
+        };
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("security");
+            command.args(["find-generic-password", "-s", service, "-a", account, "-w"]);
+            command
+        } else if cfg!(unix) {
+            let mut command = Command::new("secret-tool");
+            command.args(["lookup", "service", service, "account", account]);
+            command
+        } else {
+            return Err("the keyring provider isn't supported on this platform".into());
+        };
+        command.stdin(Stdio::null());
+        run_with_timeout(command, timeout)
+    }
+}
+
+fn trim_newline(mut value: String) -> String {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    value
+}
+
+fn run_with_timeout(mut command: Command, timeout: Duration) -> Result<String, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("couldn't start the command : {}", err))?;
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let out = std::thread::spawn(move || {
+        let mut string = String::new();
+        let _ = stdout.read_to_string(&mut string);
+        string
+    });
+    let err = std::thread::spawn(move || {
+        let mut string = String::new();
+        let _ = stderr.read_to_string(&mut string);
+        string
+    });
+    match child.wait_timeout(timeout) {
+        Ok(Some(status)) => {
+            let out = out.join().unwrap_or_default();
+            if status.success() {
+                Ok(trim_newline(out))
+            } else {
+                let err = err.join().unwrap_or_default();
+                Err(format!("the command failed ({}) : {}", status, err.trim()))
+            }
+        }
+        Ok(None) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(format!("the command timed out after {:?}", timeout))
+        }
+        Err(err) => Err(format!("couldn't wait for the command : {}", err)),
+    }
+}
+
+#[derive(Debug)]
+pub struct ProviderError {
+    pub variable: String,
+    pub message: String,
+}
+impl Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Couldn't resolve {} : {}", self.variable, self.message)
+    }
+}
+impl std::error::Error for ProviderError {}
+
+/// Resolves the references of one launch. Each reference is only resolved once, so variables
+/// sharing a reference don't run the same command twice.
+pub struct Resolver {
+    providers: Vec<Box<dyn SecretProvider>>,
+    cache: HashMap<String, Result<String, String>>,
+    timeout: Duration,
+}
+impl Default for Resolver {
+    fn default() -> Self {
+        let timeout = std::env::var(TIMEOUT_ENV_VAR)
+            .ok()
+            .and_then(|t| t.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        Resolver {
+            providers: vec![
+                Box::new(FileProvider),
+                Box::new(CommandProvider),
+                Box::new(KeyringProvider),
+            ],
+            cache: HashMap::new(),
+            timeout,
+        }
+    }
+}
+impl Resolver {
+    /// The provider handling `value`, and the argument to give it. `None` for plain values.
+    fn provider_for<'a>(&self, value: &'a str) -> Option<(&dyn SecretProvider, &'a str)> {
+        let (scheme, argument) = value.split_once(':')?;
+        self.providers
+            .iter()
+            .find(|p| p.scheme() == scheme)
+            .map(|p| (p.as_ref(), argument))
+    }
+    pub fn resolve_value(&mut self, value: &str) -> Result<String, String> {
+        if let Some(raw) = value.strip_prefix(RAW_PREFIX) {
+            return Ok(raw.to_owned());
+        }
+        if let Some(cached) = self.cache.get(value) {
+            return cached.clone();
+        }
+        let Some((provider, argument)) = self.provider_for(value) else {
+            return Ok(value.to_owned());
+        };
+        let resolved = provider.resolve(argument, self.timeout);
+        self.cache.insert(value.to_owned(), resolved.clone());
+        resolved
+    }
+    pub fn resolve_all(
+        &mut self,
+        envs: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, ProviderError> {
+        let mut resolved = HashMap::with_capacity(envs.len());
+        for (name, value) in envs {
+            match self.resolve_value(&value) {
+                Ok(value) => {
+                    resolved.insert(name, value);
+                }
+                Err(message) => {
+                    return Err(ProviderError {
+                        variable: name,
+                        message,
+                    })
+                }
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Instant};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A path in the temp directory unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("envmaster-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn file_provider_trims_the_newline() {
+        let path = temp_path("file-provider");
+        std::fs::write(&path, "s3cr3t value\r\n").unwrap();
+        let value = FileProvider.resolve(path.to_str().unwrap(), TIMEOUT);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(value.unwrap(), "s3cr3t value");
+        assert!(FileProvider
+            .resolve(temp_path("missing").to_str().unwrap(), TIMEOUT)
+            .is_err());
+    }
+
+    #[test]
+    fn raw_values_are_kept() {
+        let mut resolver = Resolver::default();
+        assert_eq!(resolver.resolve_value("raw:cmd:id").unwrap(), "cmd:id");
+        assert_eq!(resolver.resolve_value("plain").unwrap(), "plain");
+        assert_eq!(escape_command("cmd:id").unwrap(), "raw:cmd:id");
+        assert!(escape_command("command:id").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_provider_returns_stdout() {
+        let value = CommandProvider.resolve("echo 'two words'", TIMEOUT);
+        assert_eq!(value.unwrap(), "two words");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_provider_reports_failures() {
+        let err = CommandProvider
+            .resolve("sh -c 'echo no such entry >&2; exit 3'", TIMEOUT)
+            .unwrap_err();
+        assert!(err.contains("no such entry"), "{}", err);
+        assert!(CommandProvider.resolve("'unbalanced", TIMEOUT).is_err());
+        assert!(CommandProvider.resolve("", TIMEOUT).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_provider_times_out() {
+        let start = Instant::now();
+        let err = CommandProvider
+            .resolve("sleep 5", Duration::from_millis(100))
+            .unwrap_err();
+        assert!(err.contains("timed out"), "{}", err);
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn references_resolve_once_per_launch() {
+        let path = temp_path("cache");
+        let reference = format!("cmd:sh -c 'echo run >> {}; echo value'", path.display());
+        let mut resolver = Resolver::default();
+        let envs = HashMap::from([
+            ("FIRST".to_owned(), reference.clone()),
+            ("SECOND".to_owned(), reference.clone()),
+        ]);
+        let resolved = resolver.resolve_all(envs).unwrap();
+        assert_eq!(resolver.resolve_value(&reference).unwrap(), "value");
+        let runs = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resolved["FIRST"], "value");
+        assert_eq!(resolved["SECOND"], "value");
+        assert_eq!(runs.lines().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn errors_name_the_variable() {
+        let envs = HashMap::from([
+            ("GOOD".to_owned(), "plain".to_owned()),
+            ("BROKEN".to_owned(), "cmd:false".to_owned()),
+        ]);
+        let err = Resolver::default().resolve_all(envs).unwrap_err();
+        assert_eq!(err.variable, "BROKEN");
+        assert!(err.to_string().starts_with("Couldn't resolve BROKEN :"));
+    }
+}
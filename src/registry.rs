@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime},
+};
+
+use diesel::SqliteConnection;
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    database::{
+        delete_running_processes, establish_connection, get_configurations, get_running_processes,
+        new_running_process, DbResult,
+    },
+    models::{RunningProcess, RunningProcessInsert},
+    output::{print_json, print_table, OutputFormat},
+    store::store_dir,
+    vim::{config_target, launch_configuration, resolve_configuration, LaunchOptions},
+};
+
+/// How long `stop` waits for processes to exit before killing them.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The start time of `pid` as the system reports it, or `None` when it isn't running. Zombies,
+/// exited but not reaped yet, don't count. Empty when it runs but its start time is unknown.
+#[cfg(not(windows))]
+fn process_identity(pid: i32) -> Option<String> {
+    match Command::new("ps")
+        .args(["-o", "stat=,lstart=", "-p", &pid.to_string()])
+        .env("LC_ALL", "C")
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(out) => {
+            let out = String::from_utf8_lossy(&out.stdout);
+            let (stat, start) = out.trim().split_once(char::is_whitespace)?;
+            (!stat.starts_with('Z')).then(|| start.trim().to_owned())
+        }
+        // No ps around, fall back on signal 0, which zombies pass.
+        Err(_) => Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+            .then(String::new),
+    }
+}
+
+#[cfg(windows)]
+fn process_identity(pid: i32) -> Option<String> {
+    let out = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("(Get-Process -Id {}).StartTime.ToFileTimeUtc()", pid),
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let start = String::from_utf8_lossy(&out.stdout).trim().to_owned();
+    (out.status.success() && !start.is_empty()).then_some(start)
+}
+
+/// Whether the recorded process still runs. A pid reused by another process, after a reboot or
+/// once the pids wrapped, has another start time and doesn't count. Records without a start
+/// time only check the pid.
+pub fn is_running(process: &RunningProcess) -> bool {
+    match process_identity(process.pid) {
+        Some(identity) => {
+            process.identity.is_empty() || identity.is_empty() || identity == process.identity
+        }
+        None => false,
+    }
+}
+
+/// Asks `pid` to exit, or kills it when `force` is set.
+#[cfg(not(windows))]
+fn signal(pid: i32, force: bool) {
+    let signal = if force { "-KILL" } else { "-TERM" };
+    let _ = Command::new("kill")
+        .args([signal, &pid.to_string()])
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(windows)]
+fn signal(pid: i32, force: bool) {
+    let mut command = Command::new("taskkill");
+    command.args(["/PID", &pid.to_string()]);
+    if force {
+        command.arg("/F");
+    }
+    let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+/// Records a process started for the configuration `config_id`, returns the record id.
+pub fn record(
+    conn: &mut SqliteConnection,
+    config_id: i32,
+    name: &str,
+    pid: u32,
+    options: &LaunchOptions,
+) -> Option<i32> {
+    let process = RunningProcessInsert {
+        config_id,
+        name: name.to_owned(),
+        pid: pid as i32,
+        started_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        path: options.path.clone(),
+        clear: options.clear,
+        keep: options.keep.join(" "),
+        identity: process_identity(pid as i32).unwrap_or_default(),
+    };
+    match new_running_process(conn, &process) {
+        Ok(process) => Some(process.id),
+        Err(err) => {
+            warn!("couldn't record {} (pid {}) : {}", name, pid, err);
+            None
+        }
+    }
+}
+
+/// Blocks until no other launch of the configuration `config_id` holds its lock, then holds it
+/// until the returned file is dropped. Taken around the [`running`] check and the [`record`] of
+/// the started processes, so two launches of a single instance configuration can't both see it
+/// stopped. `None` when the lock file can't be used, the launch goes on without it.
+pub fn lock_configuration(db_path: &Path, config_id: i32) -> Option<File> {
+    let dir = store_dir(db_path).join("locks");
+    let path = dir.join(format!("{}.lock", config_id));
+    let file = std::fs::create_dir_all(&dir).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+    });
+    match file.and_then(|file| file.lock().map(|_| file)) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            warn!("couldn't lock {} : {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Forgets processes that exited, for when the launching envmaster saw them go.
+pub fn forget(db_path: &Path, ids: &[i32]) {
+    if ids.is_empty() {
+        return;
+    }
+    let mut conn = establish_connection(db_path);
+    if let Err(err) = delete_running_processes(&mut conn, ids) {
+        warn!("couldn't forget the exited processes : {}", err);
+    }
+}
+
+/// The recorded processes still alive, of one configuration or all of them. Stale records,
+/// whose process exited without envmaster noticing, are removed on the way.
+pub fn running(
+    conn: &mut SqliteConnection,
+    config_id: Option<i32>,
+) -> DbResult<Vec<RunningProcess>> {
+    let (alive, stale): (Vec<RunningProcess>, Vec<RunningProcess>) =
+        get_running_processes(conn, config_id)?
+            .into_iter()
+            .partition(is_running);
+    if !stale.is_empty() {
+        let ids: Vec<i32> = stale.iter().map(|process| process.id).collect();
+        delete_running_processes(conn, &ids)?;
+    }
+    Ok(alive)
+}
+
+/// Asks the processes to exit, kills the ones still there after [`STOP_TIMEOUT`], then forgets
+/// them. Each one is checked with [`is_running`] before being signalled, so a reused pid is left
+/// alone.
+pub fn stop(conn: &mut SqliteConnection, processes: &[RunningProcess]) -> DbResult<()> {
+    for process in processes.iter().filter(|process| is_running(process)) {
+        signal(process.pid, false);
+    }
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    let mut remaining: Vec<&RunningProcess> = processes.iter().collect();
+    while !remaining.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        remaining.retain(|process| is_running(process));
+    }
+    for process in remaining {
+        warn!(
+            "{} (pid {}) didn't exit, killing it",
+            process.name, process.pid
+        );
+        signal(process.pid, true);
+    }
+    let ids: Vec<i32> = processes.iter().map(|process| process.id).collect();
+    delete_running_processes(conn, &ids)?;
+    Ok(())
+}
+
+/// A running process as listed by `ps`.
+#[derive(Serialize)]
+struct ListedProcess<'a> {
+    configuration: &'a str,
+    #[serde(flatten)]
+    process: &'a RunningProcess,
+}
+
+/// Lists the processes envmaster started that are still running.
+pub fn ps_command(db_path: &Path, format: OutputFormat) {
+    let mut conn = establish_connection(db_path);
+    let processes = running(&mut conn, None).unwrap();
+    let names: HashMap<i32, String> = get_configurations(&mut conn)
+        .unwrap()
+        .into_iter()
+        .map(|cfg| (cfg.id, cfg.name))
+        .collect();
+    let listed: Vec<ListedProcess> = processes
+        .iter()
+        .map(|process| ListedProcess {
+            configuration: names
+                .get(&process.config_id)
+                .map(String::as_str)
+                .unwrap_or("?"),
+            process,
+        })
+        .collect();
+    match format {
+        OutputFormat::Json => print_json("processes", &listed),
+        OutputFormat::Text if listed.is_empty() => {
+            println!("No process started by envmaster is running.")
+        }
+        OutputFormat::Text | OutputFormat::Table => print_table(
+            &["CONFIGURATION", "PROCESS", "PID", "STARTED"],
+            &listed
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.configuration.to_owned(),
+                        p.process.name.clone(),
+                        p.process.pid.to_string(),
+                        p.process.started_at.clone(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
+
+/// Stops the running processes of a configuration.
+pub fn stop_command(db_path: &Path, target: Option<&str>) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    let processes = running(&mut conn, Some(config.configuration.id)).unwrap();
+    if processes.is_empty() {
+        println!("{} isn't running.", config.configuration.name);
+        return;
+    }
+    stop(&mut conn, &processes).unwrap();
+    for process in processes {
+        println!("stopped {} (pid {})", process.name, process.pid);
+    }
+}
+
+/// Stops a configuration and launches it again, with the path and environment options of its
+/// last launch.
+pub fn restart_command(db_path: &Path, target: Option<&str>) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    let processes = running(&mut conn, Some(config.configuration.id)).unwrap();
+    let options = match processes.last() {
+        Some(last) => LaunchOptions {
+            path: last.path.clone(),
+            clear: last.clear,
+            keep: last.keep.split_whitespace().map(String::from).collect(),
+        },
+        None => {
+            println!("{} isn't running, starting it.", config.configuration.name);
+            LaunchOptions::default()
+        }
+    };
+    stop(&mut conn, &processes).unwrap();
+    drop(conn);
+    launch_configuration(db_path, config, options);
+}
@@ -4,7 +4,44 @@ diesel::table! {
     configurations (id) {
         id -> Integer,
         name -> Text,
-        exec -> Integer,
+        exec -> Nullable<Integer>,
+        parent -> Nullable<Integer>,
+        single_instance -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    configuration_hooks (id) {
+        id -> Integer,
+        config_id -> Integer,
+        position -> Integer,
+        stage -> Text,
+        command -> Text,
+        on_failure -> Text,
+    }
+}
+
+diesel::table! {
+    configuration_processes (id) {
+        id -> Integer,
+        config_id -> Integer,
+        exec_id -> Integer,
+        position -> Integer,
+        args -> Text,
+        cwd -> Nullable<Text>,
+        mode -> Nullable<Text>,
+        stop_others -> Bool,
+    }
+}
+
+diesel::table! {
+    configuration_variables (id) {
+        id -> Integer,
+        config_id -> Integer,
+        name -> Text,
+        value -> Text,
+        secret -> Bool,
+        unset -> Bool,
     }
 }
 
@@ -13,6 +50,9 @@ diesel::table! {
         id -> Integer,
         name -> Text,
         value -> Text,
+        secret -> Bool,
+        unset -> Bool,
+        condition -> Nullable<Text>,
     }
 }
 
@@ -22,6 +62,7 @@ diesel::table! {
         name -> Text,
         executable -> Text,
         mode -> Text,
+        condition -> Nullable<Text>,
     }
 }
 
@@ -29,6 +70,7 @@ diesel::table! {
     group_environments (id) {
         id -> Integer,
         name -> Text,
+        condition -> Nullable<Text>,
     }
 }
 
@@ -40,6 +82,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    m_to_m_group_includes (id) {
+        id -> Integer,
+        group_id -> Integer,
+        included_id -> Integer,
+        position -> Integer,
+    }
+}
+
 diesel::table! {
     m_to_m_group_envs (id) {
         id -> Integer,
@@ -48,17 +99,41 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    running_processes (id) {
+        id -> Integer,
+        config_id -> Integer,
+        name -> Text,
+        pid -> Integer,
+        started_at -> Text,
+        path -> Nullable<Text>,
+        clear -> Bool,
+        keep -> Text,
+        identity -> Text,
+    }
+}
+
+diesel::joinable!(configuration_hooks -> configurations (config_id));
+diesel::joinable!(configuration_processes -> configurations (config_id));
+diesel::joinable!(configuration_processes -> executables (exec_id));
+diesel::joinable!(configuration_variables -> configurations (config_id));
 diesel::joinable!(configurations -> executables (exec));
 diesel::joinable!(m_to_m_group_configs -> configurations (config_id));
 diesel::joinable!(m_to_m_group_configs -> group_environments (group_id));
 diesel::joinable!(m_to_m_group_envs -> environments (env_id));
 diesel::joinable!(m_to_m_group_envs -> group_environments (group_id));
+diesel::joinable!(running_processes -> configurations (config_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    configuration_hooks,
+    configuration_processes,
+    configuration_variables,
     configurations,
     environments,
     executables,
     group_environments,
     m_to_m_group_configs,
     m_to_m_group_envs,
+    m_to_m_group_includes,
+    running_processes,
 );
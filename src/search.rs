@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use crate::{
+    database::{establish_connection, search},
+    models::SearchPattern,
+    output::{print_json, print_table, OutputFormat},
+    secrets::{display_assignment, display_value},
+};
+
+/// Prints the variables matching `pattern` with the groups and configurations using them, then
+/// the groups and configurations whose name matches.
+pub fn find_command(
+    db_path: &Path,
+    pattern: &str,
+    regex: bool,
+    names: bool,
+    values: bool,
+    format: OutputFormat,
+) {
+    let mut search_pattern = SearchPattern::new(pattern, regex)
+        .unwrap_or_else(|err| panic!("Invalid pattern {} : {}", pattern, err));
+    // Only one of them can be given, both are searched otherwise.
+    search_pattern.names = !values;
+    search_pattern.values = !names;
+    let mut conn = establish_connection(db_path);
+    let results = search(&mut conn, &search_pattern).unwrap();
+    match format {
+        OutputFormat::Json => return print_json("search", &results),
+        OutputFormat::Table => {
+            return print_table(
+                &["NAME", "VALUE", "GROUPS", "USED BY"],
+                &results
+                    .variables
+                    .iter()
+                    .map(|var| {
+                        let uses: Vec<&str> = var
+                            .usage
+                            .configurations
+                            .iter()
+                            .map(|u| u.configuration.as_str())
+                            .collect();
+                        vec![
+                            var.environment.name.clone(),
+                            display_value(&var.environment).to_owned(),
+                            var.usage.groups.join(","),
+                            uses.join(","),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+        OutputFormat::Text => {}
+    }
+    if results.is_empty() {
+        println!("Nothing matches {}.", pattern);
+        return;
+    }
+    for var in results.variables.iter() {
+        println!("{}", display_assignment(&var.environment));
+        if !var.usage.groups.is_empty() {
+            println!("    groups : {}", var.usage.groups.join(", "));
+        }
+        let uses: Vec<String> = var
+            .usage
+            .configurations
+            .iter()
+            .map(|u| format!("{} ({})", u.configuration, u.source))
+            .collect();
+        if uses.is_empty() {
+            println!("    not used by any configuration");
+        } else {
+            println!("    used by : {}", uses.join(", "));
+        }
+    }
+    if !results.groups.is_empty() {
+        let groups: Vec<&str> = results.groups.iter().map(|g| g.name.as_str()).collect();
+        println!("Groups : {}", groups.join(", "));
+    }
+    if !results.configurations.is_empty() {
+        let configurations: Vec<&str> = results
+            .configurations
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        println!("Configurations : {}", configurations.join(", "));
+    }
+}
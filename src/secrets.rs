@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+
+use crate::{
+    models::{Environment, LinkedConfiguration},
+    store::data_dir,
+};
+
+/// When set, secrets are encrypted with a key derived from this passphrase instead of the
+/// local keyfile.
+pub const PASSPHRASE_ENV_VAR: &str = "ENVMASTER_PASSPHRASE";
+/// Shown instead of secret values.
+pub const MASK: &str = "********";
+/// Shown instead of the value of unset variables.
+pub const UNSET: &str = "(unset)";
+const KEYFILE_NAME: &str = "secret.key";
+const KEYFILE_PREFIX: &str = "secret:key:";
+const PASSPHRASE_PREFIX: &str = "secret:pass:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub enum SecretError {
+    NoPassphrase,
+    Keyfile(std::io::Error),
+    Malformed,
+    Decrypt,
+}
+impl Display for SecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretError::NoPassphrase => write!(
+                f,
+                "this secret was encrypted with a passphrase, set {} to decrypt it",
+                PASSPHRASE_ENV_VAR
+            ),
+            SecretError::Keyfile(err) => write!(f, "the keyfile couldn't be used : {}", err),
+            SecretError::Malformed => write!(f, "the stored secret is malformed"),
+            SecretError::Decrypt => write!(f, "wrong key or passphrase"),
+        }
+    }
+}
+impl std::error::Error for SecretError {}
+
+pub fn keyfile_path() -> PathBuf {
+    data_dir().join(KEYFILE_NAME)
+}
+
+/// Attempts at reading a keyfile another launch is still writing, 50 ms apart.
+const KEYFILE_READ_ATTEMPTS: usize = 20;
+
+fn read_keyfile(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut bytes = std::fs::read(path)?;
+    for _ in 1..KEYFILE_READ_ATTEMPTS {
+        if bytes.len() >= 32 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        bytes = std::fs::read(path)?;
+    }
+    Ok(bytes)
+}
+
+/// Writes a new key to `path`, which must not exist yet. Only the owner can ever read it.
+fn create_keyfile(path: &Path, key: &Key) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(key.as_slice()).inspect_err(|_| {
+        // An empty keyfile would be taken as a malformed one from then on.
+        let _ = std::fs::remove_file(path);
+    })
+}
+
+/// Reads the local keyfile, generating it on first use. When two launches generate it at the
+/// same time, the one that didn't create the file reads the other's key instead of replacing
+/// it.
+fn keyfile_key() -> Result<Key, SecretError> {
+    let path = keyfile_path();
+    let bytes = match read_keyfile(&path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            match create_keyfile(&path, &key) {
+                Ok(()) => return Ok(key),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => read_keyfile(&path),
+                Err(err) => Err(err),
+            }
+        }
+        read => read,
+    };
+    match bytes {
+        Ok(bytes) if bytes.len() == 32 => Ok(*Key::from_slice(&bytes)),
+        Ok(_) => Err(SecretError::Malformed),
+        Err(err) => Err(SecretError::Keyfile(err)),
+    }
+}
+
+fn passphrase_key(passphrase: &str, salt: &[u8]) -> Result<Key, SecretError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| SecretError::Malformed)?;
+    Ok(key)
+}
+
+fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV_VAR)
+        .ok()
+        .filter(|p| !p.is_empty())
+}
+
+/// Encrypts a value for storage, with the passphrase when one is set and the keyfile otherwise.
+pub fn encrypt(value: &str) -> Result<String, SecretError> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let (prefix, mut blob, key) = match passphrase() {
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = passphrase_key(&passphrase, &salt)?;
+            (PASSPHRASE_PREFIX, salt.to_vec(), key)
+        }
+        None => (KEYFILE_PREFIX, vec![], keyfile_key()?),
+    };
+    let cipher = ChaCha20Poly1305::new(&key);
+    let encrypted = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| SecretError::Malformed)?;
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&encrypted);
+    Ok(format!("{}{}", prefix, STANDARD.encode(blob)))
+}
+
+pub fn decrypt(stored: &str) -> Result<String, SecretError> {
+    let (blob, key) = if let Some(encoded) = stored.strip_prefix(PASSPHRASE_PREFIX) {
+        let blob = STANDARD
+            .decode(encoded)
+            .map_err(|_| SecretError::Malformed)?;
+        if blob.len() < SALT_LEN + NONCE_LEN {
+            return Err(SecretError::Malformed);
+        }
+        let passphrase = passphrase().ok_or(SecretError::NoPassphrase)?;
+        let key = passphrase_key(&passphrase, &blob[..SALT_LEN])?;
+        (blob[SALT_LEN..].to_vec(), key)
+    } else if let Some(encoded) = stored.strip_prefix(KEYFILE_PREFIX) {
+        let blob = STANDARD
+            .decode(encoded)
+            .map_err(|_| SecretError::Malformed)?;
+        (blob, keyfile_key()?)
+    } else {
+        return Err(SecretError::Malformed);
+    };
+    if blob.len() < NONCE_LEN {
+        return Err(SecretError::Malformed);
+    }
+    let (nonce, encrypted) = blob.split_at(NONCE_LEN);
+    let decrypted = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| SecretError::Decrypt)?;
+    String::from_utf8(decrypted).map_err(|_| SecretError::Malformed)
+}
+
+/// Value to show for a variable : the stored value, a mask for secrets, or [`UNSET`].
+pub fn display_value(env: &Environment) -> &str {
+    if env.unset {
+        UNSET
+    } else if env.secret {
+        MASK
+    } else {
+        &env.value
+    }
+}
+
+/// `NAME=value` as shown in listings, or `NAME (unset)`.
+pub fn display_assignment(env: &Environment) -> String {
+    if env.unset {
+        format!("{} {}", env.name, UNSET)
+    } else {
+        format!("{}={}", env.name, display_value(env))
+    }
+}
+
+/// Same as [`LinkedConfiguration::get_environments`], with secrets decrypted. Only meant to be
+/// called right before handing the variables to another process.
+pub fn reveal_environments(
+    cfg: &LinkedConfiguration,
+    include_secrets: bool,
+) -> Result<HashMap<String, String>, String> {
+    let mut envs = HashMap::new();
+    for (_, _, env) in cfg.effective_environments() {
+        if env.unset {
+            envs.remove(&env.name);
+        } else if !env.secret {
+            envs.insert(env.name.clone(), env.value.clone());
+        } else if include_secrets {
+            let value = decrypt(&env.value)
+                .map_err(|err| format!("Couldn't decrypt {} : {}", env.name, err))?;
+            envs.insert(env.name.clone(), value);
+        } else {
+            envs.remove(&env.name);
+        }
+    }
+    Ok(envs)
+}
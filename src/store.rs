@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+use log::warn;
+use serde::Serialize;
+
+use crate::output::{print_json, print_table, OutputFormat};
+
+/// Environment variable pointing at a database file, used when neither `--db` nor `--store`
+/// were given.
+pub const DB_ENV_VAR: &str = "ENVMASTER_DB";
+const DEFAULT_DB_NAME: &str = "vimming_storage.db";
+const STORES_FILE_NAME: &str = "stores.cfg";
+
+/// A named database listed in the stores file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Store {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+fn project_dirs() -> ProjectDirs {
+    match ProjectDirs::from("com", "Dekharen", "vimming") {
+        Some(proj_dirs) => proj_dirs,
+        None => panic!("Could not determine the project directories"),
+    }
+}
+
+fn ensure_dir(dir: &Path) -> PathBuf {
+    match dir.try_exists() {
+        Ok(exists) => {
+            if !exists {
+                std::fs::create_dir_all(dir).unwrap();
+            }
+        }
+        Err(err) => panic!(
+            "An error occured while acquiring the local directories : {}",
+            err
+        ),
+    }
+    dir.to_path_buf()
+}
+
+/// Local data directory, holding the default database.
+pub fn data_dir() -> PathBuf {
+    ensure_dir(project_dirs().data_local_dir())
+}
+
+/// FNV-1a, stable across builds unlike the standard hasher.
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Local directory for the files of the database at `db_path`, named after the database and a
+/// hash of its full path, so stores sharing a file name don't mix.
+pub fn store_dir(db_path: &Path) -> PathBuf {
+    // The directory is resolved rather than the file, which doesn't exist before the first
+    // connection.
+    let parent = db_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let full = std::fs::canonicalize(parent)
+        .map(|dir| dir.join(db_path.file_name().unwrap_or_default()))
+        .or_else(|_| std::path::absolute(db_path))
+        .unwrap_or_else(|_| db_path.to_path_buf());
+    let stem: String = db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let hash = stable_hash(full.as_os_str().as_encoded_bytes());
+    data_dir()
+        .join("stores")
+        .join(format!("{}-{:016x}", stem, hash))
+}
+
+/// Path of the file listing the named stores, one `name = path` per line.
+pub fn stores_file() -> PathBuf {
+    ensure_dir(project_dirs().config_dir()).join(STORES_FILE_NAME)
+}
+
+fn parse_stores(string: &str, base: &Path) -> Vec<Store> {
+    let mut stores: Vec<Store> = vec![];
+    for (line_index, line) in string.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((name, path)) => {
+                let name = name.trim();
+                let path = PathBuf::from(path.trim());
+                // Relative paths are resolved against the stores file, so the file can live in a
+                // shared repository next to the databases it lists.
+                let path = if path.is_relative() {
+                    base.join(path)
+                } else {
+                    path
+                };
+                if let Some(previous) = stores.iter_mut().find(|s| s.name == name) {
+                    warn!(
+                        "store {} will be overriden at line {}.",
+                        name,
+                        line_index + 1
+                    );
+                    previous.path = path;
+                } else {
+                    stores.push(Store {
+                        name: name.into(),
+                        path,
+                    });
+                }
+            }
+            None => {
+                warn!(
+                    "Line {} of the stores file was malformatted, expected `name = path`.",
+                    line_index + 1
+                );
+            }
+        }
+    }
+    stores
+}
+
+/// Reads the stores file. A missing file simply means no named stores.
+pub fn read_stores() -> Vec<Store> {
+    let file = stores_file();
+    match std::fs::read_to_string(&file) {
+        Ok(string) => parse_stores(&string, file.parent().unwrap_or(Path::new("."))),
+        Err(_) => vec![],
+    }
+}
+
+/// Picks the database to use : `--db` first, then `--store`, then the `ENVMASTER_DB` variable,
+/// and finally the default database in the data directory.
+pub fn resolve_db_path(db: Option<&str>, store: Option<&str>) -> PathBuf {
+    if let Some(db) = db {
+        return PathBuf::from(db);
+    }
+    if let Some(store) = store {
+        let stores = read_stores();
+        return match stores.into_iter().find(|s| s.name == store) {
+            Some(found) => found.path,
+            None => panic!(
+                "Unknown store {}. Declare it in {} as `{} = path/to/store.db`.",
+                store,
+                stores_file().display(),
+                store
+            ),
+        };
+    }
+    if let Ok(db) = std::env::var(DB_ENV_VAR) {
+        if !db.is_empty() {
+            return PathBuf::from(db);
+        }
+    }
+    data_dir().join(DEFAULT_DB_NAME)
+}
+
+/// A database as listed by `stores`.
+#[derive(Serialize)]
+struct ListedStore {
+    #[serde(flatten)]
+    store: Store,
+    /// Whether it's the database in use.
+    current: bool,
+}
+
+pub fn list_stores(current: &Path, format: OutputFormat) {
+    let default = data_dir().join(DEFAULT_DB_NAME);
+    let mut stores = vec![Store {
+        name: "default".to_owned(),
+        path: default.clone(),
+    }];
+    let named = read_stores();
+    if current != default && !named.iter().any(|s| s.path == current) {
+        stores.push(Store {
+            name: format!("(--db / {})", DB_ENV_VAR),
+            path: current.to_path_buf(),
+        });
+    }
+    stores.extend(named);
+    let listed: Vec<ListedStore> = stores
+        .into_iter()
+        .map(|store| ListedStore {
+            current: store.path == current,
+            store,
+        })
+        .collect();
+    match format {
+        OutputFormat::Json => print_json("stores", &listed),
+        OutputFormat::Table => print_table(
+            &["NAME", "PATH", "CURRENT"],
+            &listed
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.store.name.clone(),
+                        s.store.path.display().to_string(),
+                        if s.current { "*" } else { "" }.to_owned(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::Text => {
+            println!("Stores file : {}", stores_file().display());
+            for s in listed.iter() {
+                let marker = if s.current { "*" } else { " " };
+                println!("{} {} : {}", marker, s.store.name, s.store.path.display());
+            }
+        }
+    }
+}
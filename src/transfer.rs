@@ -0,0 +1,1423 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
+
+use clap::ValueEnum;
+use diesel::{Connection, SqliteConnection};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{
+        delete_linked_group_envs, delete_linked_groups_cfg, establish_connection, get_all,
+        get_configurations, get_environments, get_envs_for_group, get_executables,
+        get_group_includes, get_groups, new_configuration, new_env, new_executable,
+        new_grouped_envs, new_linked_group_envs, new_linked_groups_cfg, set_configuration_hooks,
+        set_configuration_processes, set_configuration_single_instance,
+        set_configuration_variables, set_group_includes, update_configuration, update_exec,
+        update_group, DbResult,
+    },
+    models::{
+        Environment, GroupCfgLinkInsert, GroupEnvLinkInsert, Hook, HookInsert, Process,
+        ProcessInsert, FAILURE_POLICIES, HOOK_STAGES, MODES, SINGLE_INSTANCE_POLICIES,
+    },
+    providers::escape_command,
+};
+
+/// Bumped whenever the layout of [`StoreDocument`] changes in an incompatible way.
+pub const DOCUMENT_VERSION: u32 = 1;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+impl Format {
+    /// Guesses the format from a file extension, defaulting to TOML.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// What to do when an imported entry has the same name as an existing one but differs from it.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum MergeStrategy {
+    /// Keep the existing entry untouched.
+    #[default]
+    Skip,
+    /// Replace the existing entry with the imported one.
+    Overwrite,
+    /// Import the entry under a new name.
+    Rename,
+}
+
+/// Human-readable copy of a whole store. Entries reference each other by name, so the
+/// document can be moved between databases whose ids don't match.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct StoreDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub executables: Vec<ExecutableEntry>,
+    /// Variables that don't belong to any group.
+    #[serde(default)]
+    pub variables: Vec<VariableEntry>,
+    #[serde(default)]
+    pub groups: Vec<GroupEntry>,
+    #[serde(default)]
+    pub configurations: Vec<ConfigurationEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExecutableEntry {
+    pub name: String,
+    pub executable: String,
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    /// Platform condition, entries sharing a name are variants of the same program.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+}
+fn default_mode() -> String {
+    "wait".into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VariableEntry {
+    pub name: String,
+    /// Secrets are exported encrypted, and only decrypt with the same keyfile or passphrase.
+    pub value: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+    /// Removes the variable when launching, the value is unused.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unset: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupEntry {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<VariableEntry>,
+    /// Groups whose variables come first, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigurationEntry {
+    pub name: String,
+    /// Missing when inherited from the configuration this one extends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executable: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Set on this configuration only, after its groups.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<VariableEntry>,
+    /// Started alongside the executable, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<ProcessEntry>,
+    /// Commands run before the launch and after the exit, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookEntry>,
+    /// `refuse`, `notify` or `replace` when it shouldn't run twice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub single_instance: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HookEntry {
+    /// `pre` or `post`.
+    pub stage: String,
+    pub command: String,
+    /// `abort`, `warn` or `ignore`.
+    #[serde(default = "default_on_failure")]
+    pub on_failure: String,
+}
+
+fn default_on_failure() -> String {
+    FAILURE_POLICIES[0].to_owned()
+}
+
+impl From<&Hook> for HookEntry {
+    fn from(hook: &Hook) -> Self {
+        HookEntry {
+            stage: hook.stage.clone(),
+            command: hook.command.clone(),
+            on_failure: hook.on_failure.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProcessEntry {
+    pub executable: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub args: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// The executable's mode when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stop_others: bool,
+}
+
+pub fn serialize(doc: &StoreDocument, format: Format) -> DbResult<String> {
+    let string = match format {
+        Format::Toml => toml::to_string_pretty(doc)?,
+        Format::Json => serde_json::to_string_pretty(doc)?,
+        Format::Yaml => serde_yaml::to_string(doc)?,
+    };
+    Ok(string)
+}
+
+pub fn deserialize(string: &str, format: Format) -> DbResult<StoreDocument> {
+    let doc: StoreDocument = match format {
+        Format::Toml => toml::from_str(string)?,
+        Format::Json => serde_json::from_str(string)?,
+        Format::Yaml => serde_yaml::from_str(string)?,
+    };
+    if doc.version > DOCUMENT_VERSION {
+        return Err(format!(
+            "This document was written with version {} of the format, only version {} and \
+             below are supported.",
+            doc.version, DOCUMENT_VERSION
+        )
+        .into());
+    }
+    Ok(doc)
+}
+
+/// Gives every id a unique name, suffixing duplicates with their id. Entries sharing a name
+/// but not a condition are platform variants, and keep their name.
+fn unique_names<'a>(
+    entries: impl Iterator<Item = (i32, &'a str, Option<&'a str>)>,
+    kind: &str,
+) -> HashMap<i32, String> {
+    let entries: Vec<(i32, &str, Option<&str>)> = entries.collect();
+    let mut counts: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+    for (_, name, condition) in entries.iter() {
+        *counts.entry((name, *condition)).or_default() += 1;
+    }
+    entries
+        .into_iter()
+        .map(|(id, name, condition)| {
+            if counts[&(name, condition)] > 1 {
+                let renamed = format!("{} #{}", name, id);
+                warn!(
+                    "the {} name {} is used more than once, exporting id {} as {}.",
+                    kind, name, id, renamed
+                );
+                (id, renamed)
+            } else {
+                (id, name.to_owned())
+            }
+        })
+        .collect()
+}
+
+fn variable_entry(env: &Environment) -> VariableEntry {
+    VariableEntry {
+        name: env.name.clone(),
+        value: env.value.clone(),
+        secret: env.secret,
+        unset: env.unset,
+        condition: env.condition.clone(),
+    }
+}
+
+fn process_entry<N: AsRef<str>>(process: &Process, exec_names: &HashMap<i32, N>) -> ProcessEntry {
+    ProcessEntry {
+        executable: exec_names
+            .get(&process.exec_id)
+            .map(|n| n.as_ref().to_owned())
+            .unwrap_or_default(),
+        args: process.args.clone(),
+        cwd: process.cwd.clone(),
+        mode: process.mode.clone(),
+        stop_others: process.stop_others,
+    }
+}
+
+pub fn export(conn: &mut SqliteConnection) -> DbResult<StoreDocument> {
+    let executables = get_executables(conn)?;
+    let exec_names = unique_names(
+        executables
+            .iter()
+            .map(|e| (e.id, e.name.as_str(), e.condition.as_deref())),
+        "executable",
+    );
+    let groups = get_groups(conn)?;
+    let group_names = unique_names(
+        groups.iter().map(|g| (g.id, g.name.as_str(), None)),
+        "group",
+    );
+    let mut includes: HashMap<i32, Vec<String>> = HashMap::new();
+    for include in get_group_includes(conn)? {
+        includes
+            .entry(include.group_id)
+            .or_default()
+            .push(group_names[&include.included_id].clone());
+    }
+    let mut grouped_envs: HashSet<i32> = HashSet::new();
+    let mut group_entries = vec![];
+    for group in groups.iter() {
+        let envs = get_envs_for_group(conn, group)?;
+        grouped_envs.extend(envs.iter().map(|e| e.id));
+        group_entries.push(GroupEntry {
+            name: group_names[&group.id].clone(),
+            condition: group.condition.clone(),
+            variables: envs.iter().map(variable_entry).collect(),
+            includes: includes.remove(&group.id).unwrap_or_default(),
+        });
+    }
+    let variables = get_environments(conn)?
+        .iter()
+        .filter(|env| !grouped_envs.contains(&env.id))
+        .map(variable_entry)
+        .collect();
+    let all = get_all(conn)?;
+    let cfg_names = unique_names(
+        all.iter()
+            .map(|c| (c.configuration.id, c.configuration.name.as_str(), None)),
+        "configuration",
+    );
+    let configurations = all
+        .into_iter()
+        .map(|cfg| ConfigurationEntry {
+            name: cfg_names[&cfg.configuration.id].clone(),
+            executable: cfg.configuration.exec.map(|id| exec_names[&id].clone()),
+            extends: cfg
+                .configuration
+                .parent
+                .and_then(|id| cfg_names.get(&id).cloned()),
+            groups: cfg
+                .groups
+                .iter()
+                .map(|g| group_names[&g.group.id].clone())
+                .collect(),
+            variables: cfg.variables.iter().map(variable_entry).collect(),
+            processes: cfg
+                .processes
+                .iter()
+                .map(|p| process_entry(p, &exec_names))
+                .collect(),
+            hooks: cfg.hooks.iter().map(HookEntry::from).collect(),
+            single_instance: cfg.configuration.single_instance.clone(),
+        })
+        .collect();
+    Ok(StoreDocument {
+        version: DOCUMENT_VERSION,
+        executables: executables
+            .into_iter()
+            .map(|e| ExecutableEntry {
+                name: exec_names[&e.id].clone(),
+                executable: e.executable,
+                mode: e.mode,
+                condition: e.condition,
+            })
+            .collect(),
+        variables,
+        groups: group_entries,
+        configurations,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Create,
+    Unchanged,
+    Update(Vec<String>),
+    Skip(Vec<String>),
+    Rename(String),
+    /// An identical variable is already stored under this id.
+    Reuse(i32),
+}
+
+/// One line of the import preview.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: &'static str,
+    pub name: String,
+    pub action: Action,
+}
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.action {
+            Action::Create => write!(f, "+ {} {}", self.kind, self.name),
+            Action::Unchanged => write!(f, "= {} {}", self.kind, self.name),
+            Action::Update(diff) => {
+                write!(f, "~ {} {} : {}", self.kind, self.name, diff.join(", "))
+            }
+            Action::Skip(diff) => write!(
+                f,
+                "! {} {} exists, skipped : {}",
+                self.kind,
+                self.name,
+                diff.join(", ")
+            ),
+            Action::Reuse(id) => write!(f, "& {} {} reused (id {})", self.kind, self.name, id),
+            Action::Rename(new_name) => {
+                write!(f, "> {} {} imported as {}", self.kind, self.name, new_name)
+            }
+        }
+    }
+}
+
+fn free_name(name: &str, taken: &HashSet<String>) -> String {
+    let mut candidate = format!("{} (imported)", name);
+    let mut index = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{} (imported {})", name, index);
+        index += 1;
+    }
+    candidate
+}
+
+fn set_diff<T: Ord + Display>(old: &BTreeSet<T>, new: &BTreeSet<T>) -> Vec<String> {
+    let mut diff: Vec<String> = new.difference(old).map(|x| format!("+{}", x)).collect();
+    diff.extend(old.difference(new).map(|x| format!("-{}", x)));
+    diff
+}
+
+impl Display for VariableEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.unset {
+            write!(f, "{} {}", self.name, crate::secrets::UNSET)
+        } else if self.secret {
+            write!(f, "{}={}", self.name, crate::secrets::MASK)
+        } else {
+            write!(f, "{}={}", self.name, self.value)
+        }
+    }
+}
+
+struct Importer<'a> {
+    conn: &'a mut SqliteConnection,
+    strategy: MergeStrategy,
+    changes: Vec<Change>,
+    envs: HashMap<VariableEntry, i32>,
+}
+impl Importer<'_> {
+    fn change(&mut self, kind: &'static str, name: &str, action: Action) {
+        self.changes.push(Change {
+            kind,
+            name: name.to_owned(),
+            action,
+        });
+    }
+    /// Existing variables are shared : a name and value pair already in the store is reused.
+    fn env_id(&mut self, var: &VariableEntry) -> DbResult<i32> {
+        if let Some(id) = self.envs.get(var) {
+            return Ok(*id);
+        }
+        let env = new_env(
+            self.conn,
+            &var.name,
+            &var.value,
+            var.secret,
+            var.unset,
+            var.condition.as_deref(),
+        )?;
+        self.envs.insert(var.clone(), env.id);
+        self.change("variable", &var.to_string(), Action::Create);
+        Ok(env.id)
+    }
+    /// Variables of a group being written, only created once it's known the group is.
+    fn env_ids(&mut self, vars: &[VariableEntry]) -> DbResult<Vec<i32>> {
+        vars.iter().map(|var| self.env_id(var)).collect()
+    }
+    fn executables(&mut self, entries: &[ExecutableEntry]) -> DbResult<HashMap<String, i32>> {
+        let existing = get_executables(self.conn)?;
+        let mut taken: HashSet<String> = existing.iter().map(|e| e.name.clone()).collect();
+        let mut ids: HashMap<String, i32> =
+            existing.iter().map(|e| (e.name.clone(), e.id)).collect();
+        for entry in entries {
+            let condition = entry.condition.as_deref();
+            let Some(current) = existing
+                .iter()
+                .find(|e| e.name == entry.name && e.condition == entry.condition)
+            else {
+                let exe = new_executable(
+                    self.conn,
+                    &entry.name,
+                    &entry.executable,
+                    &entry.mode,
+                    condition,
+                )?;
+                ids.entry(entry.name.clone()).or_insert(exe.id);
+                taken.insert(entry.name.clone());
+                self.change("executable", &entry.name, Action::Create);
+                continue;
+            };
+            let mut diff = vec![];
+            if current.executable != entry.executable {
+                diff.push(format!("{} -> {}", current.executable, entry.executable));
+            }
+            if current.mode != entry.mode {
+                diff.push(format!("mode {} -> {}", current.mode, entry.mode));
+            }
+            let action = if diff.is_empty() {
+                Action::Unchanged
+            } else {
+                match self.strategy {
+                    MergeStrategy::Skip => Action::Skip(diff),
+                    MergeStrategy::Overwrite => {
+                        update_exec(
+                            self.conn,
+                            &current.id,
+                            &entry.name,
+                            &entry.executable,
+                            &entry.mode,
+                            condition,
+                        )?;
+                        Action::Update(diff)
+                    }
+                    MergeStrategy::Rename => {
+                        let name = free_name(&entry.name, &taken);
+                        let exe = new_executable(
+                            self.conn,
+                            &name,
+                            &entry.executable,
+                            &entry.mode,
+                            condition,
+                        )?;
+                        ids.insert(entry.name.clone(), exe.id);
+                        taken.insert(name.clone());
+                        Action::Rename(name)
+                    }
+                }
+            };
+            self.change("executable", &entry.name, action);
+        }
+        Ok(ids)
+    }
+    fn groups(&mut self, entries: &[GroupEntry]) -> DbResult<HashMap<String, i32>> {
+        let existing = get_groups(self.conn)?;
+        let mut taken: HashSet<String> = existing.iter().map(|g| g.name.clone()).collect();
+        let mut ids: HashMap<String, i32> =
+            existing.iter().map(|g| (g.name.clone(), g.id)).collect();
+        let existing_names: HashMap<i32, &String> =
+            existing.iter().map(|g| (g.id, &g.name)).collect();
+        let mut current_includes: HashMap<i32, Vec<&str>> = HashMap::new();
+        for include in get_group_includes(self.conn)? {
+            if let Some(name) = existing_names.get(&include.included_id) {
+                current_includes
+                    .entry(include.group_id)
+                    .or_default()
+                    .push(name.as_str());
+            }
+        }
+        // Includes are set once every group exists, since a document may list a group before
+        // the ones it includes.
+        let mut written: Vec<(i32, &GroupEntry)> = vec![];
+        for entry in entries {
+            let condition = entry.condition.as_deref();
+            let Some(current) = existing.iter().find(|g| g.name == entry.name) else {
+                let group = new_grouped_envs(self.conn, &entry.name, condition)?;
+                let env_ids = self.env_ids(&entry.variables)?;
+                self.link_envs(group.id, &env_ids)?;
+                ids.insert(entry.name.clone(), group.id);
+                taken.insert(entry.name.clone());
+                written.push((group.id, entry));
+                self.change("group", &entry.name, Action::Create);
+                continue;
+            };
+            let current_envs = get_envs_for_group(self.conn, current)?;
+            let old: BTreeSet<VariableEntry> = current_envs.iter().map(variable_entry).collect();
+            let new: BTreeSet<VariableEntry> = entry.variables.iter().cloned().collect();
+            let mut diff = set_diff(&old, &new);
+            if current.condition != entry.condition {
+                diff.push(format!(
+                    "condition {} -> {}",
+                    current.condition.as_deref().unwrap_or("none"),
+                    condition.unwrap_or("none")
+                ));
+            }
+            let old_includes = current_includes.remove(&current.id).unwrap_or_default();
+            if old_includes != entry.includes {
+                diff.push(format!(
+                    "includes [{}] -> [{}]",
+                    old_includes.join(", "),
+                    entry.includes.join(", ")
+                ));
+            }
+            let action = if diff.is_empty() {
+                Action::Unchanged
+            } else {
+                match self.strategy {
+                    MergeStrategy::Skip => Action::Skip(diff),
+                    MergeStrategy::Overwrite => {
+                        update_group(self.conn, &current.id, &entry.name, condition)?;
+                        let old_ids: Vec<i32> = current_envs.iter().map(|e| e.id).collect();
+                        delete_linked_group_envs(self.conn, &old_ids, current.id)?;
+                        let env_ids = self.env_ids(&entry.variables)?;
+                        self.link_envs(current.id, &env_ids)?;
+                        written.push((current.id, entry));
+                        Action::Update(diff)
+                    }
+                    MergeStrategy::Rename => {
+                        let name = free_name(&entry.name, &taken);
+                        let group = new_grouped_envs(self.conn, &name, condition)?;
+                        let env_ids = self.env_ids(&entry.variables)?;
+                        self.link_envs(group.id, &env_ids)?;
+                        ids.insert(entry.name.clone(), group.id);
+                        taken.insert(name.clone());
+                        written.push((group.id, entry));
+                        Action::Rename(name)
+                    }
+                }
+            };
+            self.change("group", &entry.name, action);
+        }
+        for (id, entry) in written {
+            let mut included = vec![];
+            for name in entry.includes.iter() {
+                match ids.get(name) {
+                    Some(included_id) => included.push(*included_id),
+                    None => {
+                        return Err(format!(
+                            "The group {} includes the group {}, which is neither in the \
+                             document nor in the store.",
+                            entry.name, name
+                        )
+                        .into())
+                    }
+                }
+            }
+            set_group_includes(self.conn, id, &included)
+                .map_err(|err| format!("{} : {}", entry.name, err))?;
+        }
+        Ok(ids)
+    }
+    fn link_envs(&mut self, group_id: i32, env_ids: &[i32]) -> DbResult<()> {
+        let unique: BTreeSet<&i32> = env_ids.iter().collect();
+        let links: Vec<GroupEnvLinkInsert> = unique
+            .into_iter()
+            .map(|env_id| GroupEnvLinkInsert {
+                group_id: &group_id,
+                env_id,
+            })
+            .collect();
+        new_linked_group_envs(self.conn, &links)?;
+        Ok(())
+    }
+    fn link_groups(&mut self, config_id: i32, group_ids: &[i32]) -> DbResult<()> {
+        let unique: BTreeSet<&i32> = group_ids.iter().collect();
+        let links: Vec<GroupCfgLinkInsert> = unique
+            .into_iter()
+            .map(|group_id| GroupCfgLinkInsert {
+                group_id,
+                config_id: &config_id,
+            })
+            .collect();
+        new_linked_groups_cfg(self.conn, &links)?;
+        Ok(())
+    }
+    fn configurations(
+        &mut self,
+        entries: &[ConfigurationEntry],
+        executables: &HashMap<String, i32>,
+        groups: &HashMap<String, i32>,
+    ) -> DbResult<()> {
+        let existing = get_all(self.conn)?;
+        let mut taken: HashSet<String> = existing
+            .iter()
+            .map(|c| c.configuration.name.clone())
+            .collect();
+        // Executables sharing a name are platform variants, so they compare by name.
+        let exec_names: HashMap<i32, String> = get_executables(self.conn)?
+            .into_iter()
+            .map(|e| (e.id, e.name))
+            .collect();
+        let existing_names: HashMap<i32, &String> = existing
+            .iter()
+            .map(|c| (c.configuration.id, &c.configuration.name))
+            .collect();
+        let mut ids: HashMap<String, i32> = existing
+            .iter()
+            .map(|c| (c.configuration.name.clone(), c.configuration.id))
+            .collect();
+        // Parents are set once every configuration exists, since a document may list a child
+        // before the configuration it extends.
+        let mut written: Vec<(i32, &ConfigurationEntry)> = vec![];
+        for entry in entries {
+            let exec_id = match &entry.executable {
+                Some(executable) => match executables.get(executable) {
+                    Some(id) => Some(*id),
+                    None => {
+                        return Err(format!(
+                            "The configuration {} uses the executable {}, which is neither in \
+                             the document nor in the store.",
+                            entry.name, executable
+                        )
+                        .into())
+                    }
+                },
+                None => None,
+            };
+            let mut group_ids = vec![];
+            for group in entry.groups.iter() {
+                match groups.get(group) {
+                    Some(id) => group_ids.push(*id),
+                    None => {
+                        return Err(format!(
+                            "The configuration {} uses the group {}, which is neither in the \
+                             document nor in the store.",
+                            entry.name, group
+                        )
+                        .into())
+                    }
+                }
+            }
+            let Some(current) = existing.iter().find(|c| c.configuration.name == entry.name) else {
+                let cfg = new_configuration(self.conn, &entry.name, exec_id, None)?;
+                self.link_groups(cfg.id, &group_ids)?;
+                taken.insert(entry.name.clone());
+                ids.insert(entry.name.clone(), cfg.id);
+                written.push((cfg.id, entry));
+                self.change("configuration", &entry.name, Action::Create);
+                continue;
+            };
+            let mut diff = vec![];
+            let exec_name = |id: Option<i32>| id.and_then(|id| exec_names.get(&id));
+            if exec_name(current.configuration.exec) != exec_name(exec_id) {
+                let name = |id: Option<i32>| {
+                    exec_name(id)
+                        .map(|n| n.as_str())
+                        .unwrap_or("(inherited)")
+                        .to_owned()
+                };
+                diff.push(format!(
+                    "executable {} -> {}",
+                    name(current.configuration.exec),
+                    name(exec_id)
+                ));
+            }
+            let current_parent = current
+                .configuration
+                .parent
+                .and_then(|id| existing_names.get(&id).map(|n| n.as_str()));
+            if current_parent != entry.extends.as_deref() {
+                diff.push(format!(
+                    "extends {} -> {}",
+                    current_parent.unwrap_or("nothing"),
+                    entry.extends.as_deref().unwrap_or("nothing")
+                ));
+            }
+            let old: BTreeSet<String> = current
+                .groups
+                .iter()
+                .map(|g| g.group.name.clone())
+                .collect();
+            let new: BTreeSet<String> = entry.groups.iter().cloned().collect();
+            diff.extend(set_diff(&old, &new));
+            let old: BTreeSet<VariableEntry> =
+                current.variables.iter().map(variable_entry).collect();
+            let new: BTreeSet<VariableEntry> = entry.variables.iter().cloned().collect();
+            diff.extend(set_diff(&old, &new));
+            let old: Vec<ProcessEntry> = current
+                .processes
+                .iter()
+                .map(|p| process_entry(p, &exec_names))
+                .collect();
+            if old != entry.processes {
+                diff.push(format!(
+                    "processes {} -> {}",
+                    old.len(),
+                    entry.processes.len()
+                ));
+            }
+            let old: Vec<HookEntry> = current.hooks.iter().map(HookEntry::from).collect();
+            if old != entry.hooks {
+                diff.push(format!("hooks {} -> {}", old.len(), entry.hooks.len()));
+            }
+            if current.configuration.single_instance != entry.single_instance {
+                diff.push(format!(
+                    "single instance {} -> {}",
+                    current
+                        .configuration
+                        .single_instance
+                        .as_deref()
+                        .unwrap_or("off"),
+                    entry.single_instance.as_deref().unwrap_or("off")
+                ));
+            }
+            let action = if diff.is_empty() {
+                Action::Unchanged
+            } else {
+                match self.strategy {
+                    MergeStrategy::Skip => Action::Skip(diff),
+                    MergeStrategy::Overwrite => {
+                        let id = current.configuration.id;
+                        update_configuration(self.conn, &id, &entry.name, exec_id, None)?;
+                        let old_ids: Vec<i32> = current.groups.iter().map(|g| g.group.id).collect();
+                        delete_linked_groups_cfg(self.conn, &old_ids, id)?;
+                        self.link_groups(id, &group_ids)?;
+                        written.push((id, entry));
+                        Action::Update(diff)
+                    }
+                    MergeStrategy::Rename => {
+                        let name = free_name(&entry.name, &taken);
+                        let cfg = new_configuration(self.conn, &name, exec_id, None)?;
+                        self.link_groups(cfg.id, &group_ids)?;
+                        taken.insert(name.clone());
+                        ids.insert(entry.name.clone(), cfg.id);
+                        written.push((cfg.id, entry));
+                        Action::Rename(name)
+                    }
+                }
+            };
+            self.change("configuration", &entry.name, action);
+        }
+        for (id, entry) in written {
+            let variables: Vec<Environment> = entry
+                .variables
+                .iter()
+                .map(|var| Environment {
+                    id: 0,
+                    name: var.name.clone(),
+                    value: var.value.clone(),
+                    secret: var.secret,
+                    unset: var.unset,
+                    condition: None,
+                })
+                .collect();
+            set_configuration_variables(self.conn, id, &variables)?;
+            let mut processes = vec![];
+            for process in entry.processes.iter() {
+                let Some(exec_id) = executables.get(&process.executable) else {
+                    return Err(format!(
+                        "The configuration {} starts the executable {}, which is neither in the \
+                         document nor in the store.",
+                        entry.name, process.executable
+                    )
+                    .into());
+                };
+                if let Some(mode) = process.mode.as_deref().filter(|m| !MODES.contains(m)) {
+                    return Err(format!(
+                        "The configuration {} starts {} in the unknown mode {}.",
+                        entry.name, process.executable, mode
+                    )
+                    .into());
+                }
+                processes.push(ProcessInsert {
+                    config_id: id,
+                    exec_id: *exec_id,
+                    position: 0,
+                    args: process.args.clone(),
+                    cwd: process.cwd.clone(),
+                    mode: process.mode.clone(),
+                    stop_others: process.stop_others,
+                });
+            }
+            set_configuration_processes(self.conn, id, &processes)?;
+            let mut hooks = vec![];
+            for hook in entry.hooks.iter() {
+                if !HOOK_STAGES.contains(&hook.stage.as_str())
+                    || !FAILURE_POLICIES.contains(&hook.on_failure.as_str())
+                {
+                    return Err(format!(
+                        "The hook `{}` of {} needs a stage among {:?} and a failure policy \
+                         among {:?}.",
+                        hook.command, entry.name, HOOK_STAGES, FAILURE_POLICIES
+                    )
+                    .into());
+                }
+                hooks.push(HookInsert {
+                    config_id: id,
+                    position: 0,
+                    stage: hook.stage.clone(),
+                    command: hook.command.clone(),
+                    on_failure: hook.on_failure.clone(),
+                });
+            }
+            set_configuration_hooks(self.conn, id, &hooks)?;
+            if let Some(policy) = entry
+                .single_instance
+                .as_deref()
+                .filter(|p| !SINGLE_INSTANCE_POLICIES.contains(p))
+            {
+                return Err(format!(
+                    "The configuration {} has the unknown single instance policy {}, expected \
+                     one of {:?}.",
+                    entry.name, policy, SINGLE_INSTANCE_POLICIES
+                )
+                .into());
+            }
+            set_configuration_single_instance(self.conn, id, entry.single_instance.as_deref())?;
+            let parent = match &entry.extends {
+                Some(parent) => match ids.get(parent) {
+                    Some(parent_id) => Some(*parent_id),
+                    None => {
+                        return Err(format!(
+                            "The configuration {} extends {}, which is neither in the document \
+                             nor in the store.",
+                            entry.name, parent
+                        )
+                        .into())
+                    }
+                },
+                None => None,
+            };
+            if parent.is_some() {
+                let cfg = get_configurations(self.conn)?
+                    .into_iter()
+                    .find(|c| c.id == id)
+                    .ok_or("A configuration disappeared during the import.")?;
+                update_configuration(self.conn, &id, &cfg.name, cfg.exec, parent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` with an importer inside a single transaction, which is rolled back when `dry_run` is
+/// set : the returned changes are then only a preview.
+fn run_import<F>(
+    conn: &mut SqliteConnection,
+    strategy: MergeStrategy,
+    dry_run: bool,
+    f: F,
+) -> DbResult<Vec<Change>>
+where
+    F: FnOnce(&mut Importer) -> DbResult<()>,
+{
+    let mut changes = vec![];
+    let result = conn.transaction::<(), Box<dyn std::error::Error + Send + Sync>, _>(|conn| {
+        let envs = get_environments(conn)?
+            .into_iter()
+            .map(|env| (variable_entry(&env), env.id))
+            .collect();
+        let mut importer = Importer {
+            conn,
+            strategy,
+            changes: vec![],
+            envs,
+        };
+        let result = f(&mut importer);
+        changes = importer.changes;
+        result?;
+        if dry_run {
+            return Err(Box::new(diesel::result::Error::RollbackTransaction));
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => Ok(changes),
+        Err(err) => match err.downcast_ref::<diesel::result::Error>() {
+            Some(diesel::result::Error::RollbackTransaction) if dry_run => Ok(changes),
+            _ => Err(err),
+        },
+    }
+}
+
+fn warn_escaped(name: &str) {
+    warn!(
+        "{} runs a command at launch, it is imported as text. Pass --allow-commands to keep it.",
+        name
+    );
+}
+
+/// Keeps the `cmd:` value of `var` from running, see [`escape_command`]. Secrets are decrypted
+/// to be checked, the ones that don't decrypt here can't run a command either.
+fn escape_entry(var: &mut VariableEntry) -> DbResult<()> {
+    if var.unset {
+        return Ok(());
+    }
+    if var.secret {
+        let Ok(value) = crate::secrets::decrypt(&var.value) else {
+            return Ok(());
+        };
+        if let Some(escaped) = escape_command(&value) {
+            var.value = crate::secrets::encrypt(&escaped)?;
+            warn_escaped(&var.name);
+        }
+    } else if let Some(escaped) = escape_command(&var.value) {
+        var.value = escaped;
+        warn_escaped(&var.name);
+    }
+    Ok(())
+}
+
+/// Escapes every `cmd:` value of `doc`, in groups, configurations or on their own.
+fn escape_commands(doc: &mut StoreDocument) -> DbResult<()> {
+    let groups = doc.groups.iter_mut().flat_map(|g| g.variables.iter_mut());
+    let configurations = doc
+        .configurations
+        .iter_mut()
+        .flat_map(|c| c.variables.iter_mut());
+    for var in doc.variables.iter_mut().chain(groups).chain(configurations) {
+        escape_entry(var)?;
+    }
+    Ok(())
+}
+
+/// Merges a document into the store. Its `cmd:` values are imported as text unless
+/// `allow_commands` is set, so importing a shared document never runs its commands.
+pub fn import(
+    conn: &mut SqliteConnection,
+    doc: &StoreDocument,
+    strategy: MergeStrategy,
+    dry_run: bool,
+    allow_commands: bool,
+) -> DbResult<Vec<Change>> {
+    let escaped;
+    let doc = if allow_commands {
+        doc
+    } else {
+        let mut copy = doc.clone();
+        escape_commands(&mut copy)?;
+        escaped = copy;
+        &escaped
+    };
+    run_import(conn, strategy, dry_run, |importer| {
+        for var in doc.variables.iter() {
+            if importer.envs.contains_key(var) {
+                importer.change("variable", &var.to_string(), Action::Unchanged);
+            } else {
+                importer.env_id(var)?;
+            }
+        }
+        let executables = importer.executables(&doc.executables)?;
+        let groups = importer.groups(&doc.groups)?;
+        importer.configurations(&doc.configurations, &executables, &groups)?;
+        Ok(())
+    })
+}
+
+/// Adds variables to the group named `group_name`, which is created when missing. Stored rows
+/// with the same name and value are reused, and a variable the group already holds under
+/// another value is swapped for the imported one. Secret values are encrypted first, so they
+/// never match a stored row. `cmd:` values are imported as text unless `allow_commands` is set.
+pub fn import_variables(
+    conn: &mut SqliteConnection,
+    group_name: &str,
+    vars: &[(String, String)],
+    secret: bool,
+    dry_run: bool,
+    allow_commands: bool,
+) -> DbResult<Vec<Change>> {
+    run_import(conn, MergeStrategy::Overwrite, dry_run, |importer| {
+        let existing = get_groups(importer.conn)?;
+        let (group_id, current) = match existing.iter().find(|g| g.name == group_name) {
+            Some(group) => (group.id, get_envs_for_group(importer.conn, group)?),
+            None => {
+                let group = new_grouped_envs(importer.conn, group_name, None)?;
+                importer.change("group", group_name, Action::Create);
+                (group.id, vec![])
+            }
+        };
+        let mut linked = vec![];
+        let mut unlinked = vec![];
+        for (name, value) in vars {
+            let value = match escape_command(value) {
+                Some(escaped) if !allow_commands => {
+                    warn_escaped(name);
+                    escaped
+                }
+                _ => value.clone(),
+            };
+            let var = VariableEntry {
+                name: name.clone(),
+                value: if secret {
+                    crate::secrets::encrypt(&value)?
+                } else {
+                    value
+                },
+                secret,
+                unset: false,
+                condition: None,
+            };
+            if current.iter().any(|e| variable_entry(e) == var) {
+                importer.change("variable", &var.to_string(), Action::Unchanged);
+                continue;
+            }
+            let id = match importer.envs.get(&var).copied() {
+                Some(id) => {
+                    importer.change("variable", &var.to_string(), Action::Reuse(id));
+                    id
+                }
+                None => importer.env_id(&var)?,
+            };
+            for old in current.iter().filter(|e| e.name == var.name) {
+                importer.change(
+                    "group",
+                    group_name,
+                    Action::Update(vec![format!("-{}", variable_entry(old))]),
+                );
+                unlinked.push(old.id);
+            }
+            linked.push(id);
+        }
+        if !unlinked.is_empty() {
+            delete_linked_group_envs(importer.conn, &unlinked, group_id)?;
+        }
+        if !linked.is_empty() {
+            importer.link_envs(group_id, &linked)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn export_command(db_path: &Path, format: Option<Format>, output: Option<&Path>) {
+    let mut conn = establish_connection(db_path);
+    let format = format.unwrap_or_else(|| output.map(Format::from_path).unwrap_or(Format::Toml));
+    let doc = export(&mut conn).unwrap_or_else(|err| panic!("Couldn't export the store : {}", err));
+    let string = serialize(&doc, format).unwrap_or_else(|err| panic!("Couldn't format : {}", err));
+    match output {
+        Some(path) => {
+            std::fs::write(path, string)
+                .unwrap_or_else(|err| panic!("Couldn't write {} : {}", path.display(), err));
+        }
+        None => print!("{}", string),
+    }
+}
+
+pub fn import_command(
+    db_path: &Path,
+    file: &Path,
+    format: Option<Format>,
+    strategy: MergeStrategy,
+    dry_run: bool,
+    allow_commands: bool,
+) {
+    let format = format.unwrap_or_else(|| Format::from_path(file));
+    let string = std::fs::read_to_string(file)
+        .unwrap_or_else(|err| panic!("Couldn't read {} : {}", file.display(), err));
+    let doc = deserialize(&string, format)
+        .unwrap_or_else(|err| panic!("Couldn't parse {} : {}", file.display(), err));
+    let mut conn = establish_connection(db_path);
+    match import(&mut conn, &doc, strategy, dry_run, allow_commands) {
+        Ok(changes) => {
+            for change in changes.iter() {
+                println!("{}", change);
+            }
+            if dry_run {
+                println!("Dry run, nothing was written.");
+            }
+        }
+        Err(err) => panic!("Import aborted, nothing was written : {}", err),
+    }
+}
+
+pub fn import_variables_command(
+    db_path: &Path,
+    group: &str,
+    file: Option<&Path>,
+    from_env: &[String],
+    secret: bool,
+    dry_run: bool,
+    allow_commands: bool,
+) {
+    let mut vars = vec![];
+    if let Some(file) = file {
+        let string = std::fs::read_to_string(file)
+            .unwrap_or_else(|err| panic!("Couldn't read {} : {}", file.display(), err));
+        vars = crate::dotenv::parse(&string)
+            .unwrap_or_else(|err| panic!("Couldn't parse {} : {}", file.display(), err));
+    }
+    vars.extend(crate::dotenv::from_current_env(from_env));
+    if vars.is_empty() {
+        panic!("Nothing to import, give a file and/or variables to copy with --from-env.");
+    }
+    let mut conn = establish_connection(db_path);
+    match import_variables(&mut conn, group, &vars, secret, dry_run, allow_commands) {
+        Ok(changes) => {
+            for change in changes.iter() {
+                println!("{}", change);
+            }
+            if dry_run {
+                println!("Dry run, nothing was written.");
+            }
+        }
+        Err(err) => panic!("Import aborted, nothing was written : {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty store with every migration applied.
+    fn memory() -> SqliteConnection {
+        establish_connection(Path::new(":memory:"))
+    }
+
+    fn var(name: &str, value: &str) -> VariableEntry {
+        VariableEntry {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            secret: false,
+            unset: false,
+            condition: None,
+        }
+    }
+
+    fn group(name: &str, variables: Vec<VariableEntry>, includes: &[&str]) -> GroupEntry {
+        GroupEntry {
+            name: name.to_owned(),
+            condition: None,
+            variables,
+            includes: includes.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+
+    fn document() -> StoreDocument {
+        StoreDocument {
+            version: DOCUMENT_VERSION,
+            executables: vec![
+                ExecutableEntry {
+                    name: "editor".to_owned(),
+                    executable: "/usr/bin/vim".to_owned(),
+                    mode: default_mode(),
+                    condition: None,
+                },
+                ExecutableEntry {
+                    name: "server".to_owned(),
+                    executable: "/usr/bin/python3".to_owned(),
+                    mode: default_mode(),
+                    condition: None,
+                },
+            ],
+            variables: vec![var("LOOSE", "alone")],
+            groups: vec![
+                group("extra", vec![var("EXTRA", "1")], &["base"]),
+                group(
+                    "base",
+                    vec![var("HOST", "localhost"), var("PORT", "80")],
+                    &[],
+                ),
+            ],
+            configurations: vec![ConfigurationEntry {
+                name: "dev".to_owned(),
+                executable: Some("editor".to_owned()),
+                extends: None,
+                groups: vec!["extra".to_owned()],
+                variables: vec![var("DEBUG", "true")],
+                processes: vec![ProcessEntry {
+                    executable: "server".to_owned(),
+                    args: "-m http.server".to_owned(),
+                    cwd: None,
+                    mode: None,
+                    stop_others: false,
+                }],
+                hooks: vec![HookEntry {
+                    stage: HOOK_STAGES[0].to_owned(),
+                    command: "make".to_owned(),
+                    on_failure: default_on_failure(),
+                }],
+                single_instance: None,
+            }],
+        }
+    }
+
+    fn json(doc: &StoreDocument) -> String {
+        serialize(doc, Format::Json).unwrap()
+    }
+
+    fn group_entry<'a>(doc: &'a StoreDocument, name: &str) -> Option<&'a GroupEntry> {
+        doc.groups.iter().find(|g| g.name == name)
+    }
+
+    fn imported(doc: &StoreDocument, strategy: MergeStrategy) -> SqliteConnection {
+        let mut conn = memory();
+        import(&mut conn, doc, strategy, false, true).unwrap();
+        conn
+    }
+
+    fn export_of(doc: &StoreDocument) -> StoreDocument {
+        export(&mut imported(doc, MergeStrategy::Skip)).unwrap()
+    }
+
+    #[test]
+    fn export_reimports_identically() {
+        let exported = export(&mut imported(&document(), MergeStrategy::Skip)).unwrap();
+        assert_eq!(exported.executables, document().executables);
+        assert_eq!(exported.variables, document().variables);
+        assert_eq!(exported.groups.len(), 2);
+        assert_eq!(group_entry(&exported, "extra").unwrap().includes, ["base"]);
+        let string = serialize(&exported, Format::Toml).unwrap();
+        let parsed = deserialize(&string, Format::Toml).unwrap();
+        let reexported = export(&mut imported(&parsed, MergeStrategy::Skip)).unwrap();
+        assert_eq!(json(&reexported), json(&exported));
+    }
+
+    #[test]
+    fn importing_twice_changes_nothing() {
+        let mut conn = imported(&document(), MergeStrategy::Skip);
+        let changes = import(&mut conn, &document(), MergeStrategy::Skip, false, true).unwrap();
+        assert!(changes.iter().all(|c| c.action == Action::Unchanged));
+    }
+
+    /// The document with a different `base` group and `editor` executable.
+    fn clashing() -> StoreDocument {
+        let mut doc = document();
+        doc.groups = vec![group("base", vec![var("HOST", "example.com")], &[])];
+        doc.executables.truncate(1);
+        doc.executables[0].executable = "/usr/bin/nvim".to_owned();
+        doc.variables.clear();
+        doc.configurations.clear();
+        doc
+    }
+
+    fn import_clash(strategy: MergeStrategy) -> (Vec<Change>, StoreDocument) {
+        let mut conn = imported(&document(), MergeStrategy::Skip);
+        let changes = import(&mut conn, &clashing(), strategy, false, true).unwrap();
+        (changes, export(&mut conn).unwrap())
+    }
+
+    fn action(changes: &[Change], kind: &str, name: &str) -> Action {
+        changes
+            .iter()
+            .find(|c| c.kind == kind && c.name == name)
+            .unwrap()
+            .action
+            .clone()
+    }
+
+    #[test]
+    fn skip_keeps_the_stored_entries() {
+        let (changes, exported) = import_clash(MergeStrategy::Skip);
+        assert!(matches!(action(&changes, "group", "base"), Action::Skip(_)));
+        assert!(matches!(
+            action(&changes, "executable", "editor"),
+            Action::Skip(_)
+        ));
+        assert_eq!(json(&exported), json(&export_of(&document())));
+        assert!(!changes.iter().any(|c| c.action == Action::Create));
+    }
+
+    #[test]
+    fn overwrite_replaces_the_stored_entries() {
+        let (changes, exported) = import_clash(MergeStrategy::Overwrite);
+        assert!(matches!(
+            action(&changes, "group", "base"),
+            Action::Update(_)
+        ));
+        assert_eq!(
+            group_entry(&exported, "base").unwrap().variables,
+            [var("HOST", "example.com")]
+        );
+        assert_eq!(exported.groups.len(), 2);
+        assert_eq!(exported.executables[0].executable, "/usr/bin/nvim");
+    }
+
+    #[test]
+    fn rename_imports_beside_the_stored_entries() {
+        let (changes, exported) = import_clash(MergeStrategy::Rename);
+        assert_eq!(
+            action(&changes, "group", "base"),
+            Action::Rename("base (imported)".to_owned())
+        );
+        assert_eq!(
+            group_entry(&exported, "base").unwrap().variables,
+            document().groups[1].variables
+        );
+        assert_eq!(
+            group_entry(&exported, "base (imported)").unwrap().variables,
+            [var("HOST", "example.com")]
+        );
+        assert_eq!(
+            action(&changes, "executable", "editor"),
+            Action::Rename("editor (imported)".to_owned())
+        );
+        assert_eq!(exported.executables.len(), 3);
+    }
+
+    #[test]
+    fn dry_run_rolls_back() {
+        let mut conn = memory();
+        let changes = import(&mut conn, &document(), MergeStrategy::Skip, true, true).unwrap();
+        assert_eq!(action(&changes, "group", "base"), Action::Create);
+        assert_eq!(action(&changes, "configuration", "dev"), Action::Create);
+        assert_eq!(
+            json(&export(&mut conn).unwrap()),
+            json(&StoreDocument {
+                version: DOCUMENT_VERSION,
+                ..Default::default()
+            })
+        );
+
+        let mut conn = imported(&document(), MergeStrategy::Skip);
+        import(&mut conn, &clashing(), MergeStrategy::Overwrite, true, true).unwrap();
+        assert_eq!(
+            json(&export(&mut conn).unwrap()),
+            json(&export_of(&document()))
+        );
+        let vars = [("NEW".to_owned(), "value".to_owned())];
+        import_variables(&mut conn, "base", &vars, false, true, false).unwrap();
+        assert_eq!(
+            group_entry(&export(&mut conn).unwrap(), "base")
+                .unwrap()
+                .variables,
+            document().groups[1].variables
+        );
+    }
+
+    fn with_commands() -> StoreDocument {
+        let mut doc = document();
+        doc.variables = vec![var("TOKEN", "cmd:pass show token"), var("RAW", "raw:x")];
+        doc.groups[1].variables.push(var("GROUPED", "cmd:id -u"));
+        doc.configurations[0].variables = vec![var("OWN", "cmd:hostname")];
+        doc
+    }
+
+    fn values(doc: &StoreDocument) -> BTreeSet<String> {
+        let groups = doc.groups.iter().flat_map(|g| g.variables.iter());
+        let configurations = doc.configurations.iter().flat_map(|c| c.variables.iter());
+        doc.variables
+            .iter()
+            .chain(groups)
+            .chain(configurations)
+            .map(|v| v.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_escaped_unless_allowed() {
+        let mut conn = memory();
+        import(
+            &mut conn,
+            &with_commands(),
+            MergeStrategy::Skip,
+            false,
+            false,
+        )
+        .unwrap();
+        let escaped = values(&export(&mut conn).unwrap());
+        for value in [
+            "raw:cmd:pass show token",
+            "raw:cmd:id -u",
+            "raw:cmd:hostname",
+        ] {
+            assert!(escaped.contains(value), "{} in {:?}", value, escaped);
+        }
+        assert!(escaped.contains("raw:x"));
+        assert!(!escaped.iter().any(|v| v.starts_with("cmd:")));
+
+        let allowed = values(&export_of(&with_commands()));
+        assert!(allowed.contains("cmd:pass show token"));
+        assert!(!allowed.iter().any(|v| v.starts_with("raw:cmd:")));
+
+        let mut conn = memory();
+        let vars = [("TOKEN".to_owned(), "cmd:pass show token".to_owned())];
+        import_variables(&mut conn, "imported", &vars, false, false, false).unwrap();
+        let group = export(&mut conn).unwrap().groups.remove(0);
+        assert_eq!(group.variables, [var("TOKEN", "raw:cmd:pass show token")]);
+    }
+}
@@ -1,22 +1,36 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use diesel::SqliteConnection;
 use eframe::{App, CreationContext};
 use egui::{Color32, ComboBox, Frame, Grid, Id, Layout, Modal, RichText, ScrollArea, Stroke, Ui};
+use log::debug;
 
 use crate::{
     database::{
-        delete_env, delete_linked_groups_cfg, establish_connection, get_all, get_environments,
-        get_envs_for_group, get_executables, get_groups, update_configuration, update_env,
+        delete_env, delete_linked_groups_cfg, establish_connection, get_all, get_environment_usage,
+        get_environments, get_envs_for_group, get_executable_usage, get_executables,
+        get_group_includes, get_group_usage, get_groups, set_configuration_hooks,
+        set_configuration_processes, set_configuration_single_instance,
+        set_configuration_variables, set_group_includes, update_configuration, update_env,
         update_group,
     },
+    discover::{self, Discovered},
+    doctor,
     models::{
         hashset_comparison, DbObject as _, Environment, Executable, GroupCfgLinkInsert,
-        GroupEnvLinkInsert, GroupedEnvironment, LinkedConfiguration, LinkedGroups,
+        GroupEnvLinkInsert, GroupedEnvironment, HookInsert, LinkedConfiguration, LinkedGroups,
+        ProcessInsert, ResolvedVariable, RunningProcess, SearchPattern, SearchResults, Usage,
+        FAILURE_POLICIES, HOOK_PRE, HOOK_STAGES, MODES, OVERRIDE_SOURCE, SINGLE_INSTANCE_POLICIES,
     },
+    platform, registry,
+    secrets::{display_value, encrypt, SecretError},
+    transfer::import_variables,
 };
 
-pub fn show() -> eframe::Result {
+pub fn show(db_path: PathBuf) -> eframe::Result {
     let native_options = eframe::NativeOptions {
         renderer: eframe::Renderer::Glow,
         viewport: egui::ViewportBuilder::default()
@@ -31,7 +45,7 @@ pub fn show() -> eframe::Result {
     eframe::run_native(
         "Configuration Manager",
         native_options,
-        Box::new(|cc| Ok(Box::new(ConfigurationManager::new(cc)))),
+        Box::new(|cc| Ok(Box::new(ConfigurationManager::new(cc, &db_path)))),
     )
 }
 
@@ -39,12 +53,34 @@ pub fn show() -> eframe::Result {
 struct EnvFields {
     name: String,
     value: String,
+    secret: bool,
+    unset: bool,
+    condition: String,
     tip: bool,
 }
 
+#[derive(Default)]
+struct ProcessFields {
+    exec: DbId,
+    args: String,
+    cwd: String,
+    /// Empty to use the executable's mode.
+    mode: String,
+    stop_others: bool,
+}
+
+#[derive(Default)]
+struct HookFields {
+    /// Empty until picked, defaults to pre-launch.
+    stage: String,
+    command: String,
+    on_failure: String,
+}
+
 #[derive(Default)]
 struct GroupFields {
     group_name: String,
+    condition: String,
     tip: bool,
 }
 #[derive(Default)]
@@ -52,14 +88,117 @@ struct ExecutableFields {
     name: String,
     exec: String,
     mode: String, // TODO: selection from a list
+    condition: String,
+    /// Why the executable can't be launched, shown with a "Save anyway" button.
+    error: Option<String>,
     tip: bool,
 }
+
+/// Text field for a platform condition, see [`crate::platform`]. Returns whether it parses.
+fn condition_field(ui: &mut Ui, condition: &mut String) -> bool {
+    ui.label("Only on (optional) :");
+    ui.add(egui::TextEdit::singleline(condition).hint_text("os=linux|macos, host=work-laptop"));
+    match platform::validate(condition) {
+        Ok(()) => true,
+        Err(err) => {
+            ui.colored_label(Color32::RED, err);
+            false
+        }
+    }
+}
+
+/// Checks every executable, see [`crate::doctor`].
+fn broken_executables(executables: &HashMap<DbId, Executable>) -> HashMap<DbId, String> {
+    executables
+        .values()
+        .filter_map(|exe| {
+            doctor::validate_executable(exe)
+                .err()
+                .map(|err| (exe.id, err))
+        })
+        .collect()
+}
+
+/// Where an edited entry is used, so shared ones aren't changed by surprise.
+fn usage_label(ui: &mut Ui, usage: &Usage) {
+    if !usage.groups.is_empty() {
+        ui.label(format!("In groups : {}", usage.groups.join(", ")));
+    }
+    if usage.configurations.is_empty() {
+        ui.small("Not used by any configuration.");
+    } else {
+        let uses: Vec<String> = usage
+            .configurations
+            .iter()
+            .map(|u| format!("{} ({})", u.configuration, u.source))
+            .collect();
+        ui.colored_label(
+            Color32::YELLOW,
+            format!(
+                "Changes affect {} configurations : {}",
+                uses.len(),
+                uses.join(", ")
+            ),
+        );
+    }
+    ui.separator();
+}
+
+/// Runs the search box query, see [`crate::database::search`].
+fn search_configurations(conn: &mut SqliteConnection, search: &str) -> Option<SearchResults> {
+    if search.trim().is_empty() {
+        return None;
+    }
+    let pattern = SearchPattern::new(search.trim(), false).ok()?;
+    match crate::database::search(conn, &pattern) {
+        Ok(results) => Some(results),
+        Err(err) => {
+            log::error!("couldn't search for {} : {}", search, err);
+            None
+        }
+    }
+}
+
+/// Processes still alive by configuration, see [`crate::registry`].
+fn running_by_configuration(conn: &mut SqliteConnection) -> HashMap<DbId, Vec<RunningProcess>> {
+    let mut running: HashMap<DbId, Vec<RunningProcess>> = HashMap::new();
+    match registry::running(conn, None) {
+        Ok(processes) => {
+            for process in processes {
+                running.entry(process.config_id).or_default().push(process);
+            }
+        }
+        Err(err) => log::error!("couldn't list the running processes : {}", err),
+    }
+    running
+}
+
+/// The condition to store, `None` when the field is left empty.
+fn condition_value(condition: &str) -> Option<&str> {
+    Some(condition.trim()).filter(|c| !c.is_empty())
+}
+
+/// Text shown next to something carrying a condition.
+fn condition_label(condition: Option<&str>) -> String {
+    match condition {
+        Some(condition) if platform::applies(Some(condition)) => format!("[{}]", condition),
+        Some(condition) => format!("[{}, not here]", condition),
+        None => String::new(),
+    }
+}
 #[derive(Default)]
 struct Fields {
     configuration_name: String,
+    error: Option<String>,
     executable: ExecutableFields,
     group: GroupFields,
     env: EnvFields,
+    /// Override being added in the configuration modal.
+    variable: EnvFields,
+    /// Process being added in the configuration modal.
+    process: ProcessFields,
+    /// Hook being added in the configuration modal.
+    hook: HookFields,
     tip: bool,
 }
 #[derive(Default)]
@@ -67,6 +206,8 @@ struct EditableFields {
     configuration_fields: Fields,
 }
 type DbId = i32;
+/// Configuration being edited : id, name, groups, executable and parent as they were loaded.
+type ConfigurationSnapshot = (DbId, String, Vec<LinkedGroups>, Option<DbId>, Option<DbId>);
 
 #[derive(Default)]
 enum FieldState<T> {
@@ -79,12 +220,20 @@ enum FieldState<T> {
 struct EditableGroup {
     checkboxes: HashMap<DbId, bool>,
     env_checkboxes: HashMap<DbId, bool>,
+    /// Groups included by the group being edited, in order.
+    includes: Vec<DbId>,
+    error: Option<String>,
 }
 
 #[derive(Default)]
 struct EditableConfiguration {
     exec: EditableExecutable,
     groups: EditableGroup,
+    parent: Option<DbId>,
+    variables: Vec<Environment>,
+    processes: Vec<ProcessInsert>,
+    hooks: Vec<HookInsert>,
+    single_instance: Option<String>,
 }
 #[derive(Default)]
 struct EditableExecutable {
@@ -95,14 +244,42 @@ struct EditableExecutable {
 struct ModalState<T> {
     field: FieldState<T>,
     open: bool,
+    /// Where the edited entry is used, looked up when the modal opens.
+    usage: Option<Usage>,
 }
 #[derive(Default)]
 struct Modals {
-    main_state: ModalState<(DbId, String, Vec<LinkedGroups>, DbId)>,
+    main_state: ModalState<ConfigurationSnapshot>,
     exec_state: ModalState<(DbId, String, String, String)>,
     group_state: ModalState<(DbId, String, Vec<i32>)>,
-    env_state: ModalState<(DbId, String, String)>,
+    env_state: ModalState<(DbId, String, String, bool)>,
     show_env: ShowEnvModal,
+    import_env: ImportEnvModal,
+    discover: DiscoverModal,
+    resolved: ResolvedModal,
+}
+#[derive(Default)]
+struct ResolvedModal {
+    show: bool,
+    name: String,
+    variables: Vec<ResolvedVariable>,
+}
+#[derive(Default)]
+struct ImportEnvModal {
+    open: bool,
+    group_name: String,
+    file: String,
+    from_env: String,
+    secret: bool,
+    /// Keep `cmd:` values as commands, see [`import_variables`].
+    allow_commands: bool,
+    preview: Vec<String>,
+}
+#[derive(Default)]
+struct DiscoverModal {
+    open: bool,
+    /// Executables found on this machine, and whether they're selected.
+    found: Vec<(Discovered, bool)>,
 }
 #[derive(Default)]
 struct ShowEnvModal {
@@ -115,9 +292,18 @@ struct ShowEnvModal {
 
 struct ConfigurationManager {
     conn: SqliteConnection,
+    /// For the connections of background work, see the "Stop" button.
+    db_path: PathBuf,
     configurations: Vec<LinkedConfiguration>,
     groups: HashMap<DbId, GroupedEnvironment>,
     executables: HashMap<DbId, Executable>,
+    /// Executables that can't be launched from this machine, with the reason.
+    broken_executables: HashMap<DbId, String>,
+    /// Processes started by envmaster and still alive, by configuration.
+    running: HashMap<DbId, Vec<RunningProcess>>,
+    search: String,
+    /// Results of `search`, `None` when it's empty.
+    search_results: Option<SearchResults>,
     environment_variables: HashMap<DbId, Environment>,
     fields: EditableFields,
     editable: EditableConfiguration,
@@ -137,8 +323,11 @@ impl App for ConfigurationManager {
                     self.modals.main_state.open = true;
                 }
 
+                if ui.button("Refresh").clicked() {
+                    self.reload();
+                }
                 if ui.button("print").clicked() {
-                    dbg!(self.configurations.clone());
+                    debug!("{:#?}", self.configurations);
                 }
                 ui.add_space(10.);
                 if self.modals.main_state.open {
@@ -156,6 +345,15 @@ impl App for ConfigurationManager {
                 if self.modals.show_env.show {
                     self.show_env_modal(ui);
                 }
+                if self.modals.import_env.open {
+                    self.import_env_modal(ui);
+                }
+                if self.modals.resolved.show {
+                    self.resolved_modal(ui);
+                }
+                if self.modals.discover.open {
+                    self.discover_modal(ui);
+                }
             })
         });
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -165,8 +363,8 @@ impl App for ConfigurationManager {
 }
 
 impl ConfigurationManager {
-    pub fn new(_cc: &CreationContext) -> Self {
-        let mut conn = establish_connection();
+    pub fn new(_cc: &CreationContext, db_path: &Path) -> Self {
+        let mut conn = establish_connection(db_path);
         //TODO error handling
         let cfgs = get_all(&mut conn).unwrap();
         let groups = get_groups(&mut conn).unwrap();
@@ -174,7 +372,9 @@ impl ConfigurationManager {
         let envs: HashMap<i32, Environment> = envs.into_iter().map(|el| (el.id, el)).collect();
         let execs = get_executables(&mut conn).unwrap();
         let executables = execs.into_iter().map(|el| (el.id, el)).collect();
-        println!("{executables:?}");
+        debug!("{executables:?}");
+        let broken_executables = broken_executables(&executables);
+        let running = running_by_configuration(&mut conn);
         let mut editable = EditableConfiguration::default();
         let fields = EditableFields::default();
         let modals = Modals::default();
@@ -192,8 +392,13 @@ impl ConfigurationManager {
             .collect();
         Self {
             conn,
+            db_path: db_path.to_path_buf(),
             editable,
             executables,
+            broken_executables,
+            running,
+            search: String::new(),
+            search_results: None,
             configurations: cfgs,
             groups,
             environment_variables: envs,
@@ -228,8 +433,40 @@ impl ConfigurationManager {
             ui.separator();
             ui.label("Configuration name :");
             ui.text_edit_singleline(&mut self.fields.configuration_fields.configuration_name);
+            let editing = match &self.modals.main_state.field {
+                FieldState::Edit(previous) => Some(previous.0),
+                FieldState::Create => None,
+            };
+            let parent_name = |id: Option<DbId>| {
+                id.and_then(|id| {
+                    self.configurations
+                        .iter()
+                        .find(|c| c.configuration.id == id)
+                        .map(|c| c.configuration.name.clone())
+                })
+            };
+            ComboBox::from_label("Extends")
+                .selected_text(parent_name(self.editable.parent).unwrap_or("Nothing".into()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.editable.parent, None, "Nothing");
+                    for cfg in self.configurations.iter() {
+                        // A configuration can't extend itself or one of its descendants.
+                        let descendant = editing.is_some_and(|id| {
+                            cfg.configuration.id == id
+                                || cfg.ancestors.iter().any(|a| a.configuration.id == id)
+                        });
+                        if !descendant {
+                            ui.selectable_value(
+                                &mut self.editable.parent,
+                                Some(cfg.configuration.id),
+                                &cfg.configuration.name,
+                            );
+                        }
+                    }
+                });
             let selected = match self.executables.get(&self.editable.exec.id) {
                 Some(exec) => &exec.name,
+                None if self.editable.parent.is_some() => "Inherited from the parent",
                 None => "No executable selected",
             };
             ComboBox::from_label("Executable")
@@ -252,6 +489,8 @@ impl ConfigurationManager {
                                     exec.executable.clone();
                                 self.fields.configuration_fields.executable.mode =
                                     exec.mode.clone();
+                                self.fields.configuration_fields.executable.condition =
+                                    exec.condition.clone().unwrap_or_default();
                                 self.modals.exec_state.field = FieldState::Edit((
                                     exec.id,
                                     exec.name.clone(),
@@ -263,16 +502,37 @@ impl ConfigurationManager {
                             ui.selectable_value(
                                 &mut self.editable.exec.id,
                                 *executable.0,
-                                &executable.1.name,
+                                format!(
+                                    "{} {}",
+                                    executable.1.name,
+                                    condition_label(executable.1.condition.as_deref())
+                                ),
                             );
                         });
                     }
+                    if self.editable.parent.is_some() {
+                        ui.selectable_value(
+                            &mut self.editable.exec.id,
+                            0,
+                            "Inherit from the parent",
+                        );
+                    }
                     ui.separator();
                     if ui.button("Add new executable").clicked() {
                         self.modals.exec_state.open = true;
                     };
+                    if ui.button("Find installed editors").clicked() {
+                        let existing: Vec<Executable> =
+                            self.executables.values().cloned().collect();
+                        self.modals.discover.found = discover::discover(&existing)
+                            .into_iter()
+                            .map(|d| (d, false))
+                            .collect();
+                        self.modals.discover.open = true;
+                    };
                 });
 
+            let mut edited_group = None;
             ComboBox::from_label("Groups")
                 .selected_text("Select your environment groups")
                 .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
@@ -292,24 +552,7 @@ impl ConfigurationManager {
                             };
                             ui.separator();
                             if ui.button("Edit").clicked() {
-                                let mut environments: HashMap<i32, bool> = self
-                                    .environment_variables
-                                    .clone()
-                                    .into_iter()
-                                    .map(|x| (x.0, false))
-                                    .collect();
-                                for env in envs.iter() {
-                                    environments.insert(env.id, true);
-                                }
-                                self.fields.configuration_fields.group.group_name =
-                                    group.1.name.clone();
-                                self.editable.groups.env_checkboxes = environments;
-                                self.modals.group_state.field = FieldState::Edit((
-                                    group.1.id,
-                                    group.1.name.clone(),
-                                    envs.iter().map(|x| x.id).collect(),
-                                ));
-                                self.modals.group_state.open = true;
+                                edited_group = Some((group.1.clone(), envs.clone()));
                             }
                             ui.separator();
                             ui.checkbox(checked, &group.1.name);
@@ -321,36 +564,72 @@ impl ConfigurationManager {
                     if ui.button("Add new group").clicked() {
                         self.modals.group_state.open = true;
                     };
+                    if ui.button("Import variables").clicked() {
+                        self.modals.import_env.open = true;
+                    };
+                });
+            if let Some((group, envs)) = edited_group {
+                self.edit_group(&group, &envs);
+            }
+            self.configuration_variables(ui);
+            self.configuration_processes(ui);
+            self.configuration_hooks(ui);
+            ComboBox::from_label("When already running")
+                .selected_text(
+                    self.editable
+                        .single_instance
+                        .as_deref()
+                        .unwrap_or("start another"),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.editable.single_instance, None, "start another");
+                    for policy in SINGLE_INSTANCE_POLICIES {
+                        ui.selectable_value(
+                            &mut self.editable.single_instance,
+                            Some(policy.to_string()),
+                            *policy,
+                        );
+                    }
                 });
             if self.fields.configuration_fields.tip {
                 ui.label(
                     "You at the very least need to set a configuration name and select an \
-                     executable.",
+                     executable or a process, or extend a configuration that has one.",
                 );
             }
+            if let Some(error) = &self.fields.configuration_fields.error {
+                ui.colored_label(Color32::RED, error);
+            }
             if ui.button("Save").clicked() {
                 if self
                     .fields
                     .configuration_fields
                     .configuration_name
                     .is_empty()
-                    || self.editable.exec.id == 0
+                    || (self.editable.exec.id == 0
+                        && self.editable.parent.is_none()
+                        && self.editable.processes.is_empty())
                 {
                     self.fields.configuration_fields.tip = true;
                 } else {
                     self.fields.configuration_fields.tip = false;
-                    match &self.modals.main_state.field {
+                    let exec = Some(self.editable.exec.id).filter(|id| *id != 0);
+                    let config_id = match &self.modals.main_state.field {
                         FieldState::Edit(previous) => {
                             if previous.1 != self.fields.configuration_fields.configuration_name
-                                || self.editable.exec.id != previous.3
+                                || exec != previous.3
+                                || self.editable.parent != previous.4
                             {
-                                update_configuration(
+                                if let Err(err) = update_configuration(
                                     &mut self.conn,
                                     &previous.0,
                                     &self.fields.configuration_fields.configuration_name,
-                                    &self.editable.exec.id,
-                                )
-                                .unwrap();
+                                    exec,
+                                    self.editable.parent,
+                                ) {
+                                    self.fields.configuration_fields.error = Some(err.to_string());
+                                    return;
+                                }
                             }
                             let ids: Vec<GroupCfgLinkInsert> = self
                                 .editable
@@ -369,7 +648,7 @@ impl ConfigurationManager {
                                 })
                                 .collect();
                             let (added, removed) = hashset_comparison(&previous.2, &ids);
-                            dbg!(&added, &removed);
+                            debug!("added {:?}, removed {:?}", added, removed);
                             if !removed.is_empty() {
                                 let remove: Vec<i32> = removed.iter().map(|x| x.id()).collect();
                                 delete_linked_groups_cfg(&mut self.conn, &remove, previous.0)
@@ -381,12 +660,14 @@ impl ConfigurationManager {
                                     added.into_iter().copied().collect();
                                 crate::database::new_linked_groups_cfg(&mut self.conn, &v).unwrap();
                             }
+                            previous.0
                         }
                         FieldState::Create => {
                             let config = crate::database::new_configuration(
                                 &mut self.conn,
                                 &self.fields.configuration_fields.configuration_name,
-                                &self.editable.exec.id,
+                                exec,
+                                self.editable.parent,
                             )
                             .unwrap();
                             let ids: Vec<GroupCfgLinkInsert> = self
@@ -406,19 +687,330 @@ impl ConfigurationManager {
                                 })
                                 .collect();
                             crate::database::new_linked_groups_cfg(&mut self.conn, &ids).unwrap();
+                            config.id
                         }
                     };
+                    set_configuration_variables(
+                        &mut self.conn,
+                        config_id,
+                        &self.editable.variables,
+                    )
+                    .unwrap();
+                    set_configuration_processes(
+                        &mut self.conn,
+                        config_id,
+                        &self.editable.processes,
+                    )
+                    .unwrap();
+                    set_configuration_hooks(&mut self.conn, config_id, &self.editable.hooks)
+                        .unwrap();
+                    set_configuration_single_instance(
+                        &mut self.conn,
+                        config_id,
+                        self.editable.single_instance.as_deref(),
+                    )
+                    .unwrap();
                     self.reload();
                     self.reload_group_checkboxes();
                     self.modals.main_state = Default::default();
+                    self.editable.parent = None;
+                    self.editable.variables.clear();
+                    self.editable.processes.clear();
+                    self.editable.hooks.clear();
+                    self.editable.single_instance = None;
+                    self.fields.configuration_fields.error = None;
                 }
             }
         });
         if modal.should_close() {
             self.reload_group_checkboxes();
             self.modals.main_state = Default::default();
+            self.editable.parent = None;
+            self.editable.variables.clear();
+            self.editable.processes.clear();
+            self.editable.hooks.clear();
+            self.editable.single_instance = None;
+            self.fields.configuration_fields.error = None;
+        }
+    }
+    /// Variables set on the configuration being edited, applied after its groups.
+    fn configuration_variables(&mut self, ui: &mut Ui) {
+        ui.separator();
+        ui.label("Overrides, applied after the groups :");
+        let mut removed = None;
+        Grid::new("GRID_CONFIGURATION_VARIABLES")
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, var) in self.editable.variables.iter().enumerate() {
+                    ui.label(&var.name);
+                    ui.label(display_value(var));
+                    if ui.button("remove").clicked() {
+                        removed = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(idx) = removed {
+            self.editable.variables.remove(idx);
+        }
+        let fields = &mut self.fields.configuration_fields.variable;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut fields.name)
+                    .hint_text("NAME")
+                    .desired_width(120.),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut fields.value)
+                    .hint_text("value")
+                    .password(fields.secret)
+                    .desired_width(160.),
+            );
+            ui.checkbox(&mut fields.secret, "Secret");
+            if ui.button("Override").clicked() {
+                if fields.name.is_empty() {
+                    fields.tip = true;
+                    return;
+                }
+                let value = if fields.secret {
+                    match encrypt(&fields.value) {
+                        Ok(value) => value,
+                        Err(err) => {
+                            self.fields.configuration_fields.error = Some(err.to_string());
+                            return;
+                        }
+                    }
+                } else {
+                    fields.value.clone()
+                };
+                let var = Environment {
+                    id: 0,
+                    name: std::mem::take(&mut fields.name),
+                    value,
+                    secret: fields.secret,
+                    unset: false,
+                    condition: None,
+                };
+                fields.value.clear();
+                fields.tip = false;
+                self.editable.variables.retain(|v| v.name != var.name);
+                self.editable.variables.push(var);
+            }
+            if ui
+                .button("Unset")
+                .on_hover_text("Remove the variable from the environment at launch")
+                .clicked()
+            {
+                if fields.name.is_empty() {
+                    fields.tip = true;
+                    return;
+                }
+                let name = std::mem::take(&mut fields.name);
+                fields.value.clear();
+                fields.tip = false;
+                self.editable.variables.retain(|v| v.name != name);
+                self.editable.variables.push(Environment {
+                    id: 0,
+                    name,
+                    value: String::new(),
+                    secret: false,
+                    unset: true,
+                    condition: None,
+                });
+            }
+        });
+        if self.fields.configuration_fields.variable.tip {
+            ui.label("An override needs a name.");
         }
     }
+    /// Processes started alongside the executable of the configuration being edited.
+    fn configuration_processes(&mut self, ui: &mut Ui) {
+        ui.separator();
+        ui.label("Processes, started in order before the executable :");
+        let exec_name = |id: &DbId| {
+            self.executables
+                .get(id)
+                .map(|e| e.name.clone())
+                .unwrap_or_default()
+        };
+        let mut moved = None;
+        let mut removed = None;
+        let count = self.editable.processes.len();
+        Grid::new("GRID_CONFIGURATION_PROCESSES")
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, process) in self.editable.processes.iter().enumerate() {
+                    ui.label(exec_name(&process.exec_id));
+                    ui.monospace(&process.args);
+                    ui.label(process.cwd.as_deref().unwrap_or("."));
+                    ui.label(process.mode.as_deref().unwrap_or("(executable's mode)"));
+                    ui.label(if process.stop_others {
+                        "stops the others"
+                    } else {
+                        ""
+                    });
+                    if ui.add_enabled(idx > 0, egui::Button::new("⏶")).clicked() {
+                        moved = Some((idx, idx - 1));
+                    }
+                    if ui
+                        .add_enabled(idx + 1 < count, egui::Button::new("⏷"))
+                        .clicked()
+                    {
+                        moved = Some((idx, idx + 1));
+                    }
+                    if ui.button("remove").clicked() {
+                        removed = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some((from, to)) = moved {
+            self.editable.processes.swap(from, to);
+        }
+        if let Some(idx) = removed {
+            self.editable.processes.remove(idx);
+        }
+        let fields = &mut self.fields.configuration_fields.process;
+        ui.horizontal(|ui| {
+            ComboBox::from_id_salt("PROCESSEXECUTABLECOMBOBOX")
+                .selected_text(exec_name(&fields.exec))
+                .show_ui(ui, |ui| {
+                    for exec in self.executables.values() {
+                        ui.selectable_value(&mut fields.exec, exec.id, &exec.name);
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut fields.args)
+                    .hint_text("arguments")
+                    .desired_width(140.),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut fields.cwd)
+                    .hint_text("directory")
+                    .desired_width(100.),
+            );
+            ComboBox::from_id_salt("PROCESSMODECOMBOBOX")
+                .selected_text(if fields.mode.is_empty() {
+                    "executable's mode"
+                } else {
+                    &fields.mode
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut fields.mode, String::new(), "executable's mode");
+                    for mode in MODES {
+                        ui.selectable_value(&mut fields.mode, mode.to_string(), *mode);
+                    }
+                });
+            ui.checkbox(&mut fields.stop_others, "Stop the others on exit");
+        });
+        let fields = &mut self.fields.configuration_fields.process;
+        let valid_args = shlex::split(&fields.args).is_some();
+        if !valid_args {
+            ui.colored_label(Color32::RED, "Unbalanced quotes in the arguments");
+        }
+        if ui
+            .add_enabled(
+                fields.exec != 0 && valid_args,
+                egui::Button::new("Add process"),
+            )
+            .clicked()
+        {
+            let fields = std::mem::take(fields);
+            self.editable.processes.push(ProcessInsert {
+                config_id: 0,
+                exec_id: fields.exec,
+                position: 0,
+                args: fields.args.trim().to_owned(),
+                cwd: Some(fields.cwd.trim().to_owned()).filter(|c| !c.is_empty()),
+                mode: Some(fields.mode).filter(|m| !m.is_empty()),
+                stop_others: fields.stop_others,
+            });
+        }
+    }
+    /// Commands run before the launch and after the exit of the configuration being edited.
+    fn configuration_hooks(&mut self, ui: &mut Ui) {
+        ui.separator();
+        ui.label("Hooks, shell commands run with the configuration's environment :");
+        let mut moved = None;
+        let mut removed = None;
+        let count = self.editable.hooks.len();
+        Grid::new("GRID_CONFIGURATION_HOOKS")
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, hook) in self.editable.hooks.iter().enumerate() {
+                    ui.label(if hook.stage == HOOK_PRE {
+                        "before"
+                    } else {
+                        "after"
+                    });
+                    ui.monospace(&hook.command);
+                    ui.label(format!("on failure : {}", hook.on_failure));
+                    if ui.add_enabled(idx > 0, egui::Button::new("⏶")).clicked() {
+                        moved = Some((idx, idx - 1));
+                    }
+                    if ui
+                        .add_enabled(idx + 1 < count, egui::Button::new("⏷"))
+                        .clicked()
+                    {
+                        moved = Some((idx, idx + 1));
+                    }
+                    if ui.button("remove").clicked() {
+                        removed = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some((from, to)) = moved {
+            self.editable.hooks.swap(from, to);
+        }
+        if let Some(idx) = removed {
+            self.editable.hooks.remove(idx);
+        }
+        let fields = &mut self.fields.configuration_fields.hook;
+        if fields.stage.is_empty() {
+            fields.stage = HOOK_PRE.to_owned();
+        }
+        if fields.on_failure.is_empty() {
+            fields.on_failure = FAILURE_POLICIES[0].to_owned();
+        }
+        ui.horizontal(|ui| {
+            ComboBox::from_id_salt("HOOKSTAGECOMBOBOX")
+                .selected_text(&fields.stage)
+                .show_ui(ui, |ui| {
+                    for stage in HOOK_STAGES {
+                        ui.selectable_value(&mut fields.stage, stage.to_string(), *stage);
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut fields.command)
+                    .hint_text("docker compose up -d")
+                    .desired_width(200.),
+            );
+            ComboBox::from_id_salt("HOOKFAILURECOMBOBOX")
+                .selected_text(&fields.on_failure)
+                .show_ui(ui, |ui| {
+                    for policy in FAILURE_POLICIES {
+                        ui.selectable_value(&mut fields.on_failure, policy.to_string(), *policy);
+                    }
+                });
+            if ui
+                .add_enabled(
+                    !fields.command.trim().is_empty(),
+                    egui::Button::new("Add hook"),
+                )
+                .clicked()
+            {
+                let fields = std::mem::take(fields);
+                self.editable.hooks.push(HookInsert {
+                    config_id: 0,
+                    position: 0,
+                    stage: fields.stage,
+                    command: fields.command.trim().to_owned(),
+                    on_failure: fields.on_failure,
+                });
+            }
+        });
+    }
     fn exec_state_modal(&mut self, ui: &mut Ui) {
         let modal_id = Id::new("CONFIG_EXEC_CREATOR");
         let sub_modal = egui::Modal::new(modal_id).show(ui.ctx(), |ui| {
@@ -428,6 +1020,15 @@ impl ConfigurationManager {
             };
             ui.vertical_centered(|ui| ui.heading(title));
             ui.separator();
+            if let FieldState::Edit(previous) = &self.modals.exec_state.field {
+                let usage = self.modals.exec_state.usage.get_or_insert_with(|| {
+                    get_executable_usage(&mut self.conn, previous.0).unwrap_or_else(|err| {
+                        log::error!("couldn't look up where it's used : {}", err);
+                        Usage::default()
+                    })
+                });
+                usage_label(ui, usage);
+            }
             ui.label("Name :");
             ui.text_edit_singleline(&mut self.fields.configuration_fields.executable.name);
             ui.label("Executable name :");
@@ -448,6 +1049,10 @@ impl ConfigurationManager {
                         "detach",
                     );
                 });
+            let valid = condition_field(
+                ui,
+                &mut self.fields.configuration_fields.executable.condition,
+            );
             if self.fields.configuration_fields.executable.tip {
                 ui.separator();
                 ui.label(
@@ -456,22 +1061,49 @@ impl ConfigurationManager {
                 );
             }
             ui.separator();
-            if ui.button("Save").clicked() {
+            let mut save = ui.button("Save").clicked();
+            let mut force = false;
+            if let Some(err) = &self.fields.configuration_fields.executable.error {
+                ui.colored_label(Color32::RED, err);
+                if ui.button("Save anyway").clicked() {
+                    save = true;
+                    force = true;
+                }
+            }
+            if save {
+                let condition =
+                    condition_value(&self.fields.configuration_fields.executable.condition);
+                // Variants for another platform can't be checked from here.
+                let check = if force || !platform::applies(condition) {
+                    Ok(())
+                } else {
+                    doctor::resolve_executable(&self.fields.configuration_fields.executable.exec)
+                        .map(|_| ())
+                };
                 if self.fields.configuration_fields.executable.name.is_empty()
                     || self.fields.configuration_fields.executable.exec.is_empty()
+                    || !valid
                 {
                     self.fields.configuration_fields.executable.tip = true;
+                } else if let Err(err) = check {
+                    self.fields.configuration_fields.executable.error = Some(err);
                 } else {
                     self.fields.configuration_fields.executable.tip = false;
+                    self.fields.configuration_fields.executable.error = None;
                     match &self.modals.exec_state.field {
                         FieldState::Edit(edit) => {
                             let id = &edit.0;
                             let name: &str = &edit.1;
                             let exec: &str = &edit.2;
                             let mode: &str = &edit.3;
+                            let stored = self
+                                .executables
+                                .get(id)
+                                .and_then(|e| e.condition.as_deref());
                             if name != self.fields.configuration_fields.executable.name
                                 || exec != self.fields.configuration_fields.executable.exec
                                 || mode != self.fields.configuration_fields.executable.mode
+                                || stored != condition
                             {
                                 crate::database::update_exec(
                                     &mut self.conn,
@@ -479,15 +1111,23 @@ impl ConfigurationManager {
                                     &self.fields.configuration_fields.executable.name,
                                     &self.fields.configuration_fields.executable.exec,
                                     &self.fields.configuration_fields.executable.mode,
+                                    condition,
                                 )
                                 .unwrap();
                             }
                         }
                         FieldState::Create => {
+                            let mode: &str =
+                                match self.fields.configuration_fields.executable.mode.as_str() {
+                                    "" => "wait",
+                                    mode => mode,
+                                };
                             let exec = crate::database::new_executable(
                                 &mut self.conn,
                                 &self.fields.configuration_fields.executable.name,
                                 &self.fields.configuration_fields.executable.exec,
+                                mode,
+                                condition,
                             )
                             .unwrap();
                             self.editable.exec.id = exec.id;
@@ -500,6 +1140,7 @@ impl ConfigurationManager {
         });
         if sub_modal.should_close() {
             self.modals.exec_state = Default::default();
+            self.fields.configuration_fields.executable.error = None;
         }
     }
     fn group_state_modal(&mut self, ui: &mut Ui) {
@@ -511,6 +1152,15 @@ impl ConfigurationManager {
             };
             ui.vertical_centered(|ui| ui.heading(title));
             ui.separator();
+            if let FieldState::Edit(previous) = &self.modals.group_state.field {
+                let usage = self.modals.group_state.usage.get_or_insert_with(|| {
+                    get_group_usage(&mut self.conn, previous.0).unwrap_or_else(|err| {
+                        log::error!("couldn't look up where it's used : {}", err);
+                        Usage::default()
+                    })
+                });
+                usage_label(ui, usage);
+            }
             ui.label("Name :");
             ui.text_edit_singleline(&mut self.fields.configuration_fields.group.group_name);
             ComboBox::from_label("Environments within group")
@@ -523,7 +1173,8 @@ impl ConfigurationManager {
                                                 This error happened because the list of envs 
                                                 and the list of checkboxes got out of sync.",
                         );
-                        let checkbox_text = String::new() + &env.1.name + " : " + &env.1.value;
+                        let checkbox_text =
+                            String::new() + &env.1.name + " : " + display_value(env.1);
                         ui.checkbox(checked, &checkbox_text);
                     }
                     ui.separator();
@@ -532,12 +1183,61 @@ impl ConfigurationManager {
                         self.modals.env_state.open = true;
                     };
                 });
+            let editing = match &self.modals.group_state.field {
+                FieldState::Edit(previous) => Some(previous.0),
+                FieldState::Create => None,
+            };
+            ui.label("Included groups, their variables come first :");
+            let includes = self.editable.groups.includes.clone();
+            for (idx, id) in includes.iter().enumerate() {
+                let Some(group) = self.groups.get(id) else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.label(&group.name);
+                    if ui.add_enabled(idx > 0, egui::Button::new("^")).clicked() {
+                        self.editable.groups.includes.swap(idx, idx - 1);
+                    }
+                    if ui
+                        .add_enabled(idx + 1 < includes.len(), egui::Button::new("v"))
+                        .clicked()
+                    {
+                        self.editable.groups.includes.swap(idx, idx + 1);
+                    }
+                    if ui.button("remove").clicked() {
+                        self.editable.groups.includes.remove(idx);
+                    }
+                });
+            }
+            ComboBox::from_label("Include a group")
+                .selected_text("Select a group")
+                .show_ui(ui, |ui| {
+                    for group in self.groups.values() {
+                        if Some(group.id) != editing
+                            && !includes.contains(&group.id)
+                            && ui.selectable_label(false, &group.name).clicked()
+                        {
+                            self.editable.groups.includes.push(group.id);
+                        }
+                    }
+                });
             if self.fields.configuration_fields.group.tip {
                 ui.label("You need to set a group name.");
             }
+            if let Some(error) = &self.editable.groups.error {
+                ui.colored_label(Color32::RED, error);
+            }
+            if let FieldState::Edit(previous) = &self.modals.group_state.field {
+                if ui.button("Import variables into this group").clicked() {
+                    self.modals.import_env.group_name = previous.1.clone();
+                    self.modals.import_env.open = true;
+                }
+            }
 
+            let valid = condition_field(ui, &mut self.fields.configuration_fields.group.condition);
             if ui.button("Save").clicked() {
-                if self.fields.configuration_fields.group.group_name.is_empty() {
+                let condition = condition_value(&self.fields.configuration_fields.group.condition);
+                if self.fields.configuration_fields.group.group_name.is_empty() || !valid {
                     self.fields.configuration_fields.group.tip = true;
                 } else {
                     match &self.modals.group_state.field {
@@ -546,6 +1246,7 @@ impl ConfigurationManager {
                             let group = crate::database::new_grouped_envs(
                                 &mut self.conn,
                                 &self.fields.configuration_fields.group.group_name,
+                                condition,
                             )
                             .unwrap();
                             let ids: Vec<GroupEnvLinkInsert> = self
@@ -565,10 +1266,15 @@ impl ConfigurationManager {
                                 })
                                 .collect();
                             crate::database::new_linked_group_envs(&mut self.conn, &ids).unwrap();
+                            set_group_includes(&mut self.conn, group.id, &includes).unwrap();
                             self.editable.groups.checkboxes.insert(group.id, true);
                         }
                         FieldState::Edit(previous) => {
                             let id = &previous.0;
+                            if let Err(err) = set_group_includes(&mut self.conn, *id, &includes) {
+                                self.editable.groups.error = Some(err.to_string());
+                                return;
+                            }
                             let title: &str = &previous.1;
                             let old_envs: &[i32] = &previous.2;
                             let new_envs: Vec<GroupEnvLinkInsert> = self
@@ -588,8 +1294,9 @@ impl ConfigurationManager {
                                 })
                                 .collect();
                             let input: &str = &self.fields.configuration_fields.group.group_name;
-                            if input != title {
-                                update_group(&mut self.conn, id, input).unwrap();
+                            let stored = self.groups.get(id).and_then(|g| g.condition.as_deref());
+                            if input != title || stored != condition {
+                                update_group(&mut self.conn, id, input, condition).unwrap();
                             }
                             let (added, removed) = hashset_comparison(old_envs, &new_envs);
                             //TODO we're wasting a lot of resources here re-creating envlinks. It's
@@ -610,12 +1317,18 @@ impl ConfigurationManager {
                     self.reload();
                     self.reload_env_checkboxes();
                     self.modals.group_state = Default::default();
+                    self.editable.groups.includes.clear();
+                    self.editable.groups.error = None;
+                    self.fields.configuration_fields.group.condition.clear();
                 }
             }
         });
         if sub_modal.should_close() {
             self.reload_env_checkboxes();
             self.modals.group_state = Default::default();
+            self.editable.groups.includes.clear();
+            self.editable.groups.error = None;
+            self.fields.configuration_fields.group.condition.clear();
         }
     }
     fn env_state_modal(&mut self, ui: &mut Ui) {
@@ -629,10 +1342,41 @@ impl ConfigurationManager {
             ui.vertical_centered(|ui| ui.heading(title));
             ui.separator();
 
+            if let Edit(previous) = &self.modals.env_state.field {
+                let usage = self.modals.env_state.usage.get_or_insert_with(|| {
+                    get_environment_usage(&mut self.conn, previous.0).unwrap_or_else(|err| {
+                        log::error!("couldn't look up where it's used : {}", err);
+                        Usage::default()
+                    })
+                });
+                usage_label(ui, usage);
+            }
             ui.label("Environment variable name :");
             ui.text_edit_singleline(&mut self.fields.configuration_fields.env.name);
             ui.label("Environment variable value :");
-            ui.text_edit_singleline(&mut self.fields.configuration_fields.env.value);
+            let keeps_secret = matches!(self.modals.env_state.field, Edit((_, _, _, true)))
+                && self.fields.configuration_fields.env.secret;
+            ui.add(
+                egui::TextEdit::singleline(&mut self.fields.configuration_fields.env.value)
+                    .password(self.fields.configuration_fields.env.secret)
+                    .hint_text(if keeps_secret {
+                        "Leave empty to keep the current secret"
+                    } else {
+                        ""
+                    }),
+            );
+            ui.checkbox(
+                &mut self.fields.configuration_fields.env.secret,
+                "Secret (encrypted, masked in lists)",
+            );
+            ui.checkbox(
+                &mut self.fields.configuration_fields.env.unset,
+                "Unset (removed from the environment at launch, the value is ignored)",
+            );
+            let needs_value = !keeps_secret && !self.fields.configuration_fields.env.unset;
+            let valid = condition_field(ui, &mut self.fields.configuration_fields.env.condition);
+            let condition =
+                condition_value(&self.fields.configuration_fields.env.condition).map(str::to_owned);
             if self.fields.configuration_fields.env.tip {
                 ui.label("You need to set both an environment variable name and value.");
             };
@@ -640,18 +1384,23 @@ impl ConfigurationManager {
 
             if ui.button("Save and close").clicked() {
                 if self.fields.configuration_fields.env.name.is_empty()
-                    || self.fields.configuration_fields.env.value.is_empty()
+                    || (self.fields.configuration_fields.env.value.is_empty() && needs_value)
+                    || !valid
                 {
                     self.fields.configuration_fields.env.tip = true;
                 } else {
                     self.fields.configuration_fields.env.tip = false;
                     //FIXME REMOVE UNWRAP
-                    if let Edit((id, _name, _value)) = &self.modals.env_state.field {
+                    let value = self.env_value_to_store().unwrap();
+                    if let Edit((id, _name, _value, _secret)) = &self.modals.env_state.field {
                         update_env(
                             &mut self.conn,
                             id,
                             &self.fields.configuration_fields.env.name,
-                            &self.fields.configuration_fields.env.value,
+                            &value,
+                            self.fields.configuration_fields.env.secret,
+                            self.fields.configuration_fields.env.unset,
+                            condition.as_deref(),
                         )
                         .unwrap();
                         self.reload();
@@ -659,7 +1408,10 @@ impl ConfigurationManager {
                         let new_env = crate::database::new_env(
                             &mut self.conn,
                             &self.fields.configuration_fields.env.name,
-                            &self.fields.configuration_fields.env.value,
+                            &value,
+                            self.fields.configuration_fields.env.secret,
+                            self.fields.configuration_fields.env.unset,
+                            condition.as_deref(),
                         )
                         .unwrap();
                         self.reload();
@@ -671,16 +1423,21 @@ impl ConfigurationManager {
             if let FieldState::Create = &self.modals.env_state.field {
                 if ui.button("Save and add more").clicked() {
                     if self.fields.configuration_fields.env.name.is_empty()
-                        || self.fields.configuration_fields.env.value.is_empty()
+                        || (self.fields.configuration_fields.env.value.is_empty() && needs_value)
+                        || !valid
                     {
                         self.fields.configuration_fields.env.tip = true;
                     } else {
                         self.fields.configuration_fields.env.tip = false;
                         //FIXME REMOVE UNWRAP
+                        let value = self.env_value_to_store().unwrap();
                         let new_env = crate::database::new_env(
                             &mut self.conn,
                             &self.fields.configuration_fields.env.name,
-                            &self.fields.configuration_fields.env.value,
+                            &value,
+                            self.fields.configuration_fields.env.secret,
+                            self.fields.configuration_fields.env.unset,
+                            condition.as_deref(),
                         )
                         .unwrap();
                         self.reload();
@@ -695,6 +1452,136 @@ impl ConfigurationManager {
             self.modals.env_state = Default::default();
         }
     }
+    /// Value to store for the variable being edited : secrets are encrypted, and an empty value
+    /// keeps the current secret.
+    fn env_value_to_store(&self) -> Result<String, SecretError> {
+        let fields = &self.fields.configuration_fields.env;
+        if fields.unset {
+            return Ok(String::new());
+        }
+        if !fields.secret {
+            return Ok(fields.value.clone());
+        }
+        match &self.modals.env_state.field {
+            FieldState::Edit((_, _, stored, true)) if fields.value.is_empty() => Ok(stored.clone()),
+            _ => encrypt(&fields.value),
+        }
+    }
+    fn import_env_modal(&mut self, ui: &mut Ui) {
+        let modal = Modal::new(Id::new("IMPORT_ENVIRONMENT_VARIABLES")).show(ui.ctx(), |ui| {
+            ui.vertical_centered(|ui| ui.heading("IMPORT VARIABLES"));
+            ui.separator();
+            ui.label("Group (created if it doesn't exist) :");
+            ui.text_edit_singleline(&mut self.modals.import_env.group_name);
+            ui.label(".env file :");
+            ui.text_edit_singleline(&mut self.modals.import_env.file);
+            ui.label("Variables from the current environment (PREFIX* selects a family) :");
+            ui.text_edit_singleline(&mut self.modals.import_env.from_env);
+            ui.checkbox(&mut self.modals.import_env.secret, "Import as secrets");
+            ui.checkbox(
+                &mut self.modals.import_env.allow_commands,
+                "Run `cmd:` values at launch",
+            )
+            .on_hover_text("Otherwise they are imported as text");
+            ui.separator();
+            let mut run = None;
+            ui.horizontal(|ui| {
+                if ui.button("Preview").clicked() {
+                    run = Some(true);
+                }
+                if ui.button("Import").clicked() {
+                    run = Some(false);
+                }
+            });
+            if let Some(dry_run) = run {
+                let state = &mut self.modals.import_env;
+                let mut vars = vec![];
+                let mut error = None;
+                if state.group_name.is_empty() {
+                    error = Some("You need to set a group name.".to_string());
+                } else if !state.file.is_empty() {
+                    match std::fs::read_to_string(&state.file) {
+                        Ok(string) => match crate::dotenv::parse(&string) {
+                            Ok(parsed) => vars = parsed,
+                            Err(err) => error = Some(err),
+                        },
+                        Err(err) => error = Some(format!("Couldn't read the file : {}", err)),
+                    }
+                }
+                let selectors: Vec<String> = state
+                    .from_env
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect();
+                vars.extend(crate::dotenv::from_current_env(&selectors));
+                if error.is_none() && vars.is_empty() {
+                    error = Some("No variables to import.".to_string());
+                }
+                state.preview = match error {
+                    Some(err) => vec![err],
+                    None => {
+                        match import_variables(
+                            &mut self.conn,
+                            &state.group_name,
+                            &vars,
+                            state.secret,
+                            dry_run,
+                            state.allow_commands,
+                        ) {
+                            Ok(changes) => changes.iter().map(|c| c.to_string()).collect(),
+                            Err(err) => vec![format!("Import aborted : {}", err)],
+                        }
+                    }
+                };
+                if !dry_run {
+                    self.reload();
+                }
+            }
+            ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+                for line in self.modals.import_env.preview.iter() {
+                    ui.monospace(line);
+                }
+            });
+        });
+        if modal.should_close() {
+            self.modals.import_env = ImportEnvModal::default();
+        }
+    }
+    fn discover_modal(&mut self, ui: &mut Ui) {
+        let modal = Modal::new(Id::new("DISCOVER_EXECUTABLES")).show(ui.ctx(), |ui| {
+            ui.vertical_centered(|ui| ui.heading("INSTALLED EDITORS"));
+            ui.separator();
+            if self.modals.discover.found.is_empty() {
+                ui.label("No known editor or shell found.");
+            }
+            for (found, selected) in self.modals.discover.found.iter_mut() {
+                let text = format!("{} ({}, {})", found.name, found.executable, found.mode);
+                if found.known {
+                    ui.add_enabled(false, egui::Checkbox::new(&mut true, text))
+                        .on_disabled_hover_text("Already stored");
+                } else {
+                    ui.checkbox(selected, text);
+                }
+            }
+            ui.separator();
+            if ui.button("Add selected").clicked() {
+                for (found, _) in self
+                    .modals
+                    .discover
+                    .found
+                    .iter()
+                    .filter(|(found, selected)| *selected && !found.known)
+                {
+                    discover::add_discovered(&mut self.conn, found).unwrap();
+                }
+                self.reload();
+                self.modals.discover = DiscoverModal::default();
+            }
+        });
+        if modal.should_close() {
+            self.modals.discover = DiscoverModal::default();
+        }
+    }
     fn reload(&mut self) {
         let conn = &mut self.conn;
         let cfgs = get_all(conn).unwrap();
@@ -706,6 +1593,9 @@ impl ConfigurationManager {
         let executables = execs.into_iter().map(|el| (el.id, el)).collect();
         let groups: HashMap<i32, GroupedEnvironment> =
             groups.into_iter().map(|el| (el.id, el)).collect();
+        self.broken_executables = broken_executables(&executables);
+        self.running = running_by_configuration(conn);
+        self.search_results = search_configurations(conn, &self.search);
         //FIXME this is nice because it doesn't reset the checkboxes if you add something else.
         //On the other hand, it's terrible because it might lead to a desync if you DELETE a group
         //or environment.
@@ -733,9 +1623,44 @@ impl ConfigurationManager {
                 ui.label(text);
             });
         } else {
+            self.search_box(ui);
             self.configurations(ui);
         }
     }
+    /// Filters the configuration grid down to the ones using a matching variable, or named like
+    /// the search.
+    fn search_box(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .hint_text("Search variables : AWS_PROFILE, *_URL, a value...")
+                    .desired_width(300.),
+            );
+            if response.changed() {
+                self.search_results = search_configurations(&mut self.conn, &self.search);
+            }
+            if let Some(results) = &self.search_results {
+                let names: Vec<String> = results
+                    .variables
+                    .iter()
+                    .map(|var| {
+                        let mut sources: Vec<&str> =
+                            var.usage.groups.iter().map(String::as_str).collect();
+                        if sources.is_empty() {
+                            sources.push(OVERRIDE_SOURCE);
+                        }
+                        format!("{} ({})", var.environment.name, sources.join(", "))
+                    })
+                    .collect();
+                ui.small(format!("{} variables match", names.len()))
+                    .on_hover_text(names.join("\n"));
+                if ui.button("Clear").clicked() {
+                    self.search.clear();
+                    self.search_results = None;
+                }
+            }
+        });
+    }
     fn show_env_modal(&mut self, ui: &mut Ui) {
         let modal = Modal::new(Id::new("SHOW_ENVIRONMENT_VARIABLES")).show(ui.ctx(), |ui| {
             let envs = self.modals.show_env.envs.clone();
@@ -755,8 +1680,12 @@ impl ConfigurationManager {
                         });
                         ui.end_row();
                         for env in envs.iter() {
-                            ui.label(&env.name);
-                            ui.label(&env.value);
+                            ui.label(format!(
+                                "{} {}",
+                                env.name,
+                                condition_label(env.condition.as_deref())
+                            ));
+                            ui.label(display_value(env));
                             ui.horizontal_centered(|ui| {
                                 if ui.button("delete").clicked() {
                                     delete_env(&mut self.conn, &env.id).unwrap();
@@ -770,11 +1699,20 @@ impl ConfigurationManager {
 
                                 if ui.button("edit").clicked() {
                                     self.fields.configuration_fields.env.name = env.name.clone();
-                                    self.fields.configuration_fields.env.value = env.value.clone();
+                                    self.fields.configuration_fields.env.value = if env.secret {
+                                        String::new()
+                                    } else {
+                                        env.value.clone()
+                                    };
+                                    self.fields.configuration_fields.env.secret = env.secret;
+                                    self.fields.configuration_fields.env.unset = env.unset;
+                                    self.fields.configuration_fields.env.condition =
+                                        env.condition.clone().unwrap_or_default();
                                     self.modals.env_state.field = FieldState::Edit((
                                         env.id,
                                         env.name.clone(),
                                         env.value.clone(),
+                                        env.secret,
                                     ));
                                     self.modals.env_state.open = true;
                                 }
@@ -788,6 +1726,41 @@ impl ConfigurationManager {
             self.modals.show_env = ShowEnvModal::default();
         }
     }
+    fn resolved_modal(&mut self, ui: &mut Ui) {
+        let modal = Modal::new(Id::new("RESOLVED_VARIABLES")).show(ui.ctx(), |ui| {
+            ui.vertical_centered(|ui| ui.heading(&self.modals.resolved.name));
+            ui.separator();
+            if self.modals.resolved.variables.is_empty() {
+                ui.heading("This configuration doesn't set any variable.");
+                return;
+            }
+            ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("GRID_RESOLVED_VARIABLES")
+                    .striped(true)
+                    .min_col_width(100.)
+                    .show(ui, |ui| {
+                        ui.heading("NAME");
+                        ui.heading("VALUE");
+                        ui.heading("FROM");
+                        ui.end_row();
+                        for var in self.modals.resolved.variables.iter() {
+                            ui.label(&var.environment.name);
+                            ui.label(display_value(&var.environment));
+                            ui.vertical(|ui| {
+                                ui.label(format!("{} / {}", var.configuration, var.source));
+                                for overridden in var.overrides.iter() {
+                                    ui.small(format!("overrides {}", display_value(overridden)));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+        if modal.should_close() {
+            self.modals.resolved = ResolvedModal::default();
+        }
+    }
     fn configurations(&mut self, ui: &mut Ui) {
         Frame::new()
             .corner_radius(10.0)
@@ -796,7 +1769,18 @@ impl ConfigurationManager {
             .show(ui, |ui| {
                 let size = ui.max_rect().size();
                 let mut col_width = 400.;
-                let xsize = (self.configurations.len() as f32).min((size.x / col_width).floor());
+                let configurations: Vec<LinkedConfiguration> = match &self.search_results {
+                    Some(results) => {
+                        let ids = results.configuration_ids();
+                        self.configurations
+                            .iter()
+                            .filter(|cfg| ids.contains(&cfg.configuration.id))
+                            .cloned()
+                            .collect()
+                    }
+                    None => self.configurations.clone(),
+                };
+                let xsize = (configurations.len() as f32).min((size.x / col_width).floor());
                 let mut x: usize = xsize as usize;
                 let leftover = if x == 0 {
                     col_width = size.x;
@@ -807,7 +1791,6 @@ impl ConfigurationManager {
                 };
                 let min_col_width = col_width + leftover;
                 ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
-                    let configurations = self.configurations.clone();
                     Grid::new(min_col_width.to_string())
                         .striped(true)
                         .min_col_width(min_col_width)
@@ -822,27 +1805,106 @@ impl ConfigurationManager {
                                             ui.add_space(12.);
                                             ui.heading(&cfg.configuration.name);
                                             ui.separator();
-                                            let exec = self
-                                                .executables
-                                                .get(&cfg.configuration.exec)
-                                                .expect(
-                                                    "If the executable doesn't exist, we have a \
-                                                     problem.",
-                                                );
                                             ui.small(format!("id : {}", &cfg.configuration.id));
+                                            if let Some(parent) = cfg.ancestors.first() {
+                                                ui.label(
+                                                    String::from("Extends : ")
+                                                        + &parent.configuration.name,
+                                                );
+                                            }
                                             ui.separator();
-                                            ui.label(
-                                                String::from("Executable Name : ") + &exec.name,
-                                            );
-                                            ui.label(
-                                                String::from("Executable Value : ")
-                                                    + &exec.executable,
-                                            );
-
-                                            ui.separator();
-                                            ui.label(
-                                                String::from("Executable Mode : ") + &exec.mode,
-                                            );
+                                            let variants: Vec<Executable> =
+                                                self.executables.values().cloned().collect();
+                                            let chosen = cfg
+                                                .executable()
+                                                .and_then(|id| self.executables.get(&id));
+                                            match chosen.map(|exec| {
+                                                platform::select_executable(exec, &variants)
+                                            }) {
+                                                Some(Some(exec)) => {
+                                                    let inherited =
+                                                        if cfg.configuration.exec.is_none() {
+                                                            " (inherited)"
+                                                        } else {
+                                                            ""
+                                                        };
+                                                    ui.label(
+                                                        String::from("Executable Name : ")
+                                                            + &exec.name
+                                                            + inherited,
+                                                    );
+                                                    ui.label(
+                                                        String::from("Executable Value : ")
+                                                            + &exec.executable,
+                                                    );
+                                                    ui.separator();
+                                                    ui.label(
+                                                        String::from("Executable Mode : ")
+                                                            + &exec.mode,
+                                                    );
+                                                    if let Some(err) =
+                                                        self.broken_executables.get(&exec.id)
+                                                    {
+                                                        ui.colored_label(
+                                                            Color32::YELLOW,
+                                                            format!("⚠ {}", err),
+                                                        );
+                                                    }
+                                                }
+                                                Some(None) => {
+                                                    ui.colored_label(
+                                                        Color32::RED,
+                                                        "No variant of the executable for this \
+                                                         platform",
+                                                    );
+                                                }
+                                                None if !cfg.processes().is_empty() => {}
+                                                None => {
+                                                    ui.colored_label(
+                                                        Color32::RED,
+                                                        "No executable, nor inherited one",
+                                                    );
+                                                }
+                                            }
+                                            for process in cfg.processes() {
+                                                let name = self
+                                                    .executables
+                                                    .get(&process.exec_id)
+                                                    .map(|e| e.name.as_str())
+                                                    .unwrap_or_default();
+                                                ui.label(format!(
+                                                    "Also starts : {} {}",
+                                                    name, process.args
+                                                ));
+                                            }
+                                            if let Some(running) =
+                                                self.running.get(&cfg.configuration.id).cloned()
+                                            {
+                                                ui.colored_label(
+                                                    Color32::GREEN,
+                                                    format!("● running ({})", running.len()),
+                                                );
+                                                if ui.button("Stop").clicked() {
+                                                    // Stopping waits for the processes to exit,
+                                                    // Refresh shows when they are gone.
+                                                    let db_path = self.db_path.clone();
+                                                    let name = cfg.configuration.name.clone();
+                                                    std::thread::spawn(move || {
+                                                        let mut conn =
+                                                            establish_connection(&db_path);
+                                                        if let Err(err) =
+                                                            registry::stop(&mut conn, &running)
+                                                        {
+                                                            log::error!(
+                                                                "couldn't stop {} : {}",
+                                                                name,
+                                                                err
+                                                            );
+                                                        }
+                                                    });
+                                                    self.running.remove(&cfg.configuration.id);
+                                                }
+                                            }
                                             ui.separator();
                                             if ui
                                                 .add(
@@ -857,7 +1919,22 @@ impl ConfigurationManager {
                                                     .configuration_fields
                                                     .configuration_name =
                                                     cfg.configuration.name.clone();
-                                                self.editable.exec.id = cfg.configuration.exec;
+                                                self.editable.exec.id =
+                                                    cfg.configuration.exec.unwrap_or(0);
+                                                self.editable.parent = cfg.configuration.parent;
+                                                self.editable.variables = cfg.variables.clone();
+                                                self.editable.processes = cfg
+                                                    .processes
+                                                    .iter()
+                                                    .map(ProcessInsert::from)
+                                                    .collect();
+                                                self.editable.hooks = cfg
+                                                    .hooks
+                                                    .iter()
+                                                    .map(HookInsert::from)
+                                                    .collect();
+                                                self.editable.single_instance =
+                                                    cfg.configuration.single_instance.clone();
                                                 for group in cfg.groups.iter() {
                                                     *self
                                                         .editable
@@ -871,6 +1948,7 @@ impl ConfigurationManager {
                                                     cfg.configuration.name.clone(),
                                                     cfg.groups.clone(),
                                                     cfg.configuration.exec,
+                                                    cfg.configuration.parent,
                                                 ));
                                                 self.modals.main_state.open = true;
                                             };
@@ -878,36 +1956,43 @@ impl ConfigurationManager {
                                             if ui
                                                 .add(
                                                     egui::Button::new(
-                                                        RichText::new("Use as Base"), // .color(Color32),
+                                                        RichText::new("Extend"), // .color(Color32),
                                                     )
                                                     .corner_radius(3.)
                                                     .fill(Color32::from_rgb(15, 15, 30)),
                                                 )
                                                 .clicked()
                                             {
-                                                //ERROR HERE
                                                 self.fields
                                                     .configuration_fields
                                                     .configuration_name =
-                                                    cfg.configuration.name.clone();
-                                                self.editable.exec.id = cfg.configuration.exec;
-                                                for group in cfg.groups.iter() {
-                                                    *self
-                                                        .editable
-                                                        .groups
-                                                        .checkboxes
-                                                        .get_mut(&group.group.id)
-                                                        .unwrap() = true;
-                                                }
+                                                    cfg.configuration.name.clone() + " (extended)";
+                                                self.editable.exec.id = 0;
+                                                self.editable.parent = Some(cfg.configuration.id);
                                                 self.modals.main_state.open = true;
                                             };
+                                            if ui.button("Resolved variables").clicked() {
+                                                self.modals.resolved = ResolvedModal {
+                                                    show: true,
+                                                    name: cfg.configuration.name.clone(),
+                                                    variables: cfg.resolve_sources(),
+                                                };
+                                            }
                                             Frame::NONE.inner_margin(5.).show(ui, |ui| {
                                                 ScrollArea::horizontal()
                                                     .id_salt(cfg.configuration.id.to_string())
                                                     .show(ui, |ui| {
                                                         ui.horizontal(|ui| {
                                                             ui.add_space(15.);
-                                                            for group in cfg.groups.iter() {
+                                                            for (from, group) in
+                                                                cfg.effective_groups()
+                                                            {
+                                                                if from.id != cfg.configuration.id {
+                                                                    ui.small(format!(
+                                                                        "from {} :",
+                                                                        from.name
+                                                                    ));
+                                                                }
                                                                 self.display_group(ui, group);
                                                             }
                                                             ui.add_space(15.);
@@ -916,6 +2001,19 @@ impl ConfigurationManager {
                                                         ui.add_space(12.);
                                                     });
                                             });
+                                            if !cfg.variables.is_empty() {
+                                                ui.label("Overrides :");
+                                                for var in cfg.variables.iter() {
+                                                    ui.colored_label(
+                                                        Color32::LIGHT_YELLOW,
+                                                        format!(
+                                                            "{}={}",
+                                                            var.name,
+                                                            display_value(var)
+                                                        ),
+                                                    );
+                                                }
+                                            }
                                         });
                                     });
 
@@ -936,7 +2034,11 @@ impl ConfigurationManager {
             .stroke(Stroke::new(0.5, Color32::WHITE))
             .show(ui, |ui| {
                 ui.vertical(|ui| {
-                    ui.label(format!("Group Name : {}", group.group.name));
+                    ui.label(format!(
+                        "Group Name : {} {}",
+                        group.group.name,
+                        condition_label(group.group.condition.as_deref())
+                    ));
                     let s = ui.min_size();
                     ui.allocate_ui_with_layout(s, Layout::top_down(egui::Align::TOP), |ui| {
                         ui.separator();
@@ -946,35 +2048,50 @@ impl ConfigurationManager {
                     //                    // ui.separator();
                     ui.horizontal(|ui| {
                         if ui.button("Show").clicked() {
-                            self.modals.show_env.group_id = Some(GroupedEnvironment {
-                                id: group.group.id,
-                                name: group.group.name.clone(),
-                            });
+                            self.modals.show_env.group_id = Some(group.group.clone());
                             self.modals.show_env.envs = group.environments.clone();
                             self.modals.show_env.show = true;
                         };
                         if ui.button("Edit").clicked() {
-                            let mut environments: HashMap<i32, bool> = self
-                                .environment_variables
-                                .clone()
-                                .into_iter()
-                                .map(|x| (x.0, false))
-                                .collect();
-                            for env in group.environments.iter() {
-                                environments.insert(env.id, true);
-                            }
-                            self.fields.configuration_fields.group.group_name =
-                                group.group.name.clone();
-                            self.editable.groups.env_checkboxes = environments;
-                            self.modals.group_state.field = FieldState::Edit((
-                                group.group.id,
-                                group.group.name.clone(),
-                                group.environments.iter().map(|i| i.id).collect(),
-                            ));
-                            self.modals.group_state.open = true;
+                            self.edit_group(&group.group, &group.environments);
                         }
                     });
+                    if !group.includes.is_empty() {
+                        ui.small("Includes :");
+                        ui.indent(("INCLUDES", group.group.id), |ui| {
+                            for include in group.includes.iter() {
+                                self.display_group(ui, include);
+                            }
+                        });
+                    }
                 });
             });
     }
+    fn edit_group(&mut self, group: &GroupedEnvironment, envs: &[Environment]) {
+        let mut environments: HashMap<i32, bool> = self
+            .environment_variables
+            .clone()
+            .into_iter()
+            .map(|x| (x.0, false))
+            .collect();
+        for env in envs.iter() {
+            environments.insert(env.id, true);
+        }
+        self.fields.configuration_fields.group.group_name = group.name.clone();
+        self.fields.configuration_fields.group.condition =
+            group.condition.clone().unwrap_or_default();
+        self.editable.groups.env_checkboxes = environments;
+        self.editable.groups.includes = get_group_includes(&mut self.conn)
+            .unwrap()
+            .into_iter()
+            .filter(|include| include.group_id == group.id)
+            .map(|include| include.included_id)
+            .collect();
+        self.modals.group_state.field = FieldState::Edit((
+            group.id,
+            group.name.clone(),
+            envs.iter().map(|x| x.id).collect(),
+        ));
+        self.modals.group_state.open = true;
+    }
 }
@@ -1,59 +1,680 @@
-use std::{collections::HashMap, process::Command};
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
 
 use diesel::SqliteConnection;
+use log::{error, info, warn};
+use serde::Serialize;
 
 use crate::{
-    database::{establish_connection, get_config, get_single_executable},
-    models::{Executable, LinkedConfiguration},
+    database::{establish_connection, get_config, get_platform_executable},
+    doctor::resolve_executable_in,
+    launch_logs::create_launch_log,
+    logger::LAUNCH_TARGET,
+    models::{
+        Executable, LinkedConfiguration, ResolvedVariable, HOOK_POST, HOOK_PRE,
+        SINGLE_INSTANCE_NOTIFY, SINGLE_INSTANCE_REFUSE, SINGLE_INSTANCE_REPLACE,
+    },
+    output::{print_json, print_table, OutputFormat},
+    providers::Resolver,
+    registry,
+    secrets::{display_assignment, display_value, reveal_environments},
 };
 type Envs = HashMap<String, String>;
-fn get_components(conn: &mut SqliteConnection, cfg: &LinkedConfiguration) -> (Envs, Executable) {
-    let envs = cfg.get_environments();
-    let executable = get_single_executable(conn, cfg.configuration.exec).unwrap();
-    (envs, executable)
+fn get_environment(cfg: &LinkedConfiguration) -> Envs {
+    let envs = reveal_environments(cfg, true).unwrap_or_else(|err| panic!("{}", err));
+    Resolver::default()
+        .resolve_all(envs)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// The `PATH` the processes of a configuration are looked up in : the one it sets, or the
+/// current one when it leaves it alone.
+fn launch_search_path(envs: &Envs, unset: &[String]) -> Option<OsString> {
+    match envs.get("PATH") {
+        Some(path) => Some(path.into()),
+        None if unset.iter().any(|name| name == "PATH") => None,
+        None => std::env::var_os("PATH"),
+    }
+}
+
+/// The variant of `exec_id` for this platform, looked up in `search_path` before anything is
+/// started. Not finding it is only a warning, the launch itself reports the real failure.
+fn get_executable(
+    conn: &mut SqliteConnection,
+    cfg: &LinkedConfiguration,
+    exec_id: i32,
+    search_path: Option<&OsStr>,
+) -> Executable {
+    let executable = get_platform_executable(conn, exec_id).unwrap_or_else(|err| panic!("{}", err));
+    if let Err(err) = resolve_executable_in(&executable.executable, search_path) {
+        warn!(
+            "the configuration {} may not launch {} : {}. See `envmaster doctor`.",
+            cfg.configuration.name, executable.name, err
+        );
+    }
+    executable
+}
+
+/// A command ready to start, see [`crate::models::Process`].
+struct Launch {
+    name: String,
+    command: Command,
+    mode: String,
+    stop_others: bool,
 }
 
-pub fn execute_configuration(args: crate::Args) {
+fn get_launches(
+    conn: &mut SqliteConnection,
+    cfg: &LinkedConfiguration,
+    path: Option<String>,
+    search_path: Option<&OsStr>,
+) -> Vec<Launch> {
+    let mut launches = vec![];
+    for process in cfg.processes() {
+        let exe = get_executable(conn, cfg, process.exec_id, search_path);
+        let mut command = Command::new(&exe.executable);
+        let args = shlex::split(&process.args).unwrap_or_else(|| {
+            panic!(
+                "Couldn't split the arguments of {} : {}",
+                exe.name, process.args
+            )
+        });
+        command.args(args);
+        if let Some(cwd) = &process.cwd {
+            command.current_dir(cwd);
+        }
+        launches.push(Launch {
+            name: exe.name,
+            command,
+            mode: process.mode.clone().unwrap_or(exe.mode),
+            stop_others: process.stop_others,
+        });
+    }
+    match cfg.executable() {
+        Some(exec_id) => {
+            let exe = get_executable(conn, cfg, exec_id, search_path);
+            let mut command = Command::new(&exe.executable);
+            if let Some(path) = path {
+                command.arg(&path);
+            }
+            launches.push(Launch {
+                name: exe.name,
+                command,
+                mode: exe.mode,
+                stop_others: false,
+            });
+        }
+        None if launches.is_empty() => panic!(
+            "The configuration {} has no executable, and doesn't inherit one.",
+            cfg.configuration.name
+        ),
+        None => {}
+    }
+    launches
+}
+
+/// A waited process, see [`supervise`].
+struct Supervised {
+    name: String,
+    child: Child,
+    stop_others: bool,
+    /// Its id in the [`crate::registry`].
+    record: Option<i32>,
+}
+
+/// Waits for the started processes. When one marked `stop_others` exits, the rest are killed.
+fn supervise(db_path: &Path, mut running: Vec<Supervised>) {
+    let report = running.len() > 1;
+    while !running.is_empty() {
+        let mut stop = false;
+        let mut exited = vec![];
+        running.retain_mut(|process| match process.child.try_wait() {
+            Ok(Some(status)) => {
+                info!(target: LAUNCH_TARGET, "{} exited ({})", process.name, status);
+                if report {
+                    println!("{} exited ({})", process.name, status);
+                }
+                stop |= process.stop_others;
+                exited.extend(process.record);
+                false
+            }
+            Ok(None) => true,
+            Err(err) => {
+                error!("Error waiting for {} : {}", process.name, err);
+                false
+            }
+        });
+        if stop {
+            for process in running.iter_mut() {
+                let _ = process.child.kill();
+                let _ = process.child.wait();
+                info!(target: LAUNCH_TARGET, "stopped {}", process.name);
+                println!("stopped {}", process.name);
+                exited.extend(process.record);
+            }
+            registry::forget(db_path, &exited);
+            return;
+        }
+        registry::forget(db_path, &exited);
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Splits a command line target into an id when it's numeric, or a name otherwise.
+pub fn config_target(target: Option<&str>) -> (Option<i32>, Option<String>) {
+    match target {
+        Some(target) => match target.parse::<i32>() {
+            Ok(id) => (Some(id), None),
+            Err(_) => (None, Some(target.to_owned())),
+        },
+        None => (None, None),
+    }
+}
+
+/// Loads the configuration matching the id or name, falling back on the `.envcfg` file of the
+/// current directory when both are missing.
+pub fn resolve_configuration(
+    conn: &mut SqliteConnection,
+    id: Option<i32>,
+    name: Option<String>,
+) -> LinkedConfiguration {
+    let config = get_config(conn, id, name).unwrap();
+    match config.into_iter().next() {
+        Some(config) => config,
+        None => {
+            println!("No results found...");
+            panic!("Ending process... No configuration found.");
+        }
+    }
+}
+
+/// A configuration's variables as printed by `resolve`.
+#[derive(Serialize)]
+struct Resolved<'a> {
+    configuration: &'a str,
+    /// From the furthest ancestor down to the configuration.
+    chain: Vec<&'a str>,
+    variables: Vec<ResolvedVariable>,
+}
+
+/// Prints the variables a configuration launches with, the configuration and group each one
+/// comes from, and the values it overrides from further up the inheritance chain.
+pub fn resolve_command(db_path: &Path, target: Option<&str>, format: OutputFormat) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    let chain: Vec<&str> = config
+        .ancestors
+        .iter()
+        .rev()
+        .chain(std::iter::once(&config))
+        .map(|c| c.configuration.name.as_str())
+        .collect();
+    let variables = config.resolve_sources();
+    match format {
+        OutputFormat::Json => {
+            return print_json(
+                "resolved",
+                &Resolved {
+                    configuration: &config.configuration.name,
+                    chain,
+                    variables,
+                },
+            )
+        }
+        OutputFormat::Table => {
+            return print_table(
+                &["NAME", "VALUE", "CONFIGURATION", "SOURCE", "OVERRIDES"],
+                &variables
+                    .iter()
+                    .map(|var| {
+                        vec![
+                            var.environment.name.clone(),
+                            display_value(&var.environment).to_owned(),
+                            var.configuration.clone(),
+                            var.source.clone(),
+                            var.overrides.len().to_string(),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+        OutputFormat::Text => {}
+    }
+    println!("{}", chain.join(" -> "));
+    for var in variables {
+        println!(
+            "{}    ({} / {})",
+            display_assignment(&var.environment),
+            var.configuration,
+            var.source
+        );
+        for overridden in var.overrides.iter() {
+            println!("    overrides {}", display_value(overridden));
+        }
+    }
+}
+
+/// Variables `--clear` keeps, so the program still finds its user, home and tools.
+pub const CLEAN_ALLOWLIST: &[&str] = &[
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "PATH",
+    "SHELL",
+    "LANG",
+    "TMPDIR",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XDG_RUNTIME_DIR",
+    "SYSTEMROOT",
+    "WINDIR",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "TEMP",
+    "TMP",
+    "COMSPEC",
+    "PATHEXT",
+];
+
+/// Starts `command` from an empty environment, except for [`CLEAN_ALLOWLIST`] and `keep`.
+fn clean_environment(command: &mut Command, keep: &[String]) {
+    command.env_clear();
+    for (name, value) in std::env::vars_os() {
+        let name_str = name.to_string_lossy();
+        let kept = CLEAN_ALLOWLIST
+            .iter()
+            .copied()
+            .chain(keep.iter().map(String::as_str))
+            .any(|allowed| {
+                // Variable names are case insensitive on windows.
+                if cfg!(windows) {
+                    allowed.eq_ignore_ascii_case(&name_str)
+                } else {
+                    allowed == name_str
+                }
+            });
+        if kept {
+            command.env(name, value);
+        }
+    }
+}
+
+/// How a configuration is launched, besides the configuration itself.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    /// Given to the configuration's executable.
+    pub path: Option<String>,
+    /// Start from a clean environment, see [`CLEAN_ALLOWLIST`].
+    pub clear: bool,
+    /// More variables to keep with `clear`.
+    pub keep: Vec<String>,
+}
+
+pub fn execute_configuration(args: crate::Args, db_path: &Path) {
     // let (mut command, is_win) = target_command();
     // let command_name = if is_win { "/C" } else { "-c" };
-    let mut conn = establish_connection();
-    let config = get_config(&mut conn, args.id, args.config).unwrap();
-    if config.is_empty() {
-        println!("No results found...");
-        panic!("Ending process... No configuration found.");
-    }
-    let (envs, exe) = get_components(&mut conn, &config[0]);
-    let mut command = Command::new(exe.executable);
+    let mut conn = establish_connection(db_path);
+    let config = resolve_configuration(&mut conn, args.id, args.config);
     // let path = &args.path;
     // let start = match path {
     //     Some(path) => exe.executable + " " + path,
     //     None => exe.executable,
     // };
     drop(conn);
-    if args.clear {
-        command.env_remove("TERM");
-        // command.env_clear();
-    }
-    if let Some(path) = args.path {
-        command.arg(&path);
-    }
-    command.envs(envs);
-    let mode: &str = &exe.mode;
-    match mode {
-        "wait" => {
-            let _ = command
-                .spawn()
-                .expect("failed to execute process")
-                .wait()
-                .expect("Error waiting for command ");
-        }
-        "detach" => {
-            #[allow(clippy::zombie_processes)] // We are intentionally calling another process then
-            // exiting. We just want to make sure the process was started.
-            let _ = command.spawn().expect("failed to execute process");
-        }
-        _ => {
-            panic!("Unknown mode of operation (maybe unimplemented ?)");
+    let options = LaunchOptions {
+        path: args.path,
+        clear: args.clear,
+        keep: args.keep,
+    };
+    launch_configuration(db_path, config, options);
+}
+
+/// Gives a command the environment of `config` : its resolved variables `envs`, minus the unset
+/// ones, on top of the current environment or a clean one.
+fn environment_setup<'a>(
+    config: &LinkedConfiguration,
+    envs: Envs,
+    options: &'a LaunchOptions,
+) -> impl Fn(&mut Command) + 'a {
+    let unset = config.unset_variables();
+    move |command: &mut Command| {
+        if options.clear {
+            clean_environment(command, &options.keep);
+        }
+        for name in unset.iter() {
+            command.env_remove(name);
+        }
+        command.envs(&envs);
+    }
+}
+
+/// Runs `argv` with the environment of a configuration instead of its executable, and exits
+/// with its status. Hooks and processes of the configuration are left out.
+pub fn run_command(db_path: &Path, target: Option<&str>, argv: &[String], options: LaunchOptions) {
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    drop(conn);
+    let Some((program, args)) = argv.split_first() else {
+        panic!("Nothing to run, give the command after `--`.");
+    };
+    let mut command = Command::new(program);
+    command.args(args);
+    environment_setup(&config, get_environment(&config), &options)(&mut command);
+    info!(
+        target: LAUNCH_TARGET,
+        "running `{}` with {}",
+        argv.join(" "),
+        config.configuration.name
+    );
+    let status = command
+        .status()
+        .unwrap_or_else(|err| panic!("Couldn't run {} : {}", program, err));
+    info!(target: LAUNCH_TARGET, "`{}` exited ({})", argv.join(" "), status);
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// Set in the shells started by [`subshell_command`], to the name of their configuration.
+pub const ACTIVE_VARIABLE: &str = "ENVMASTER_ACTIVE";
+
+/// The user's interactive shell.
+fn user_shell() -> String {
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_owned())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_owned())
+    }
+}
+
+/// Prefixes the prompt of `shell` with the configuration name. Bash reads its prompt from its rc
+/// file, so it gets one that loads the user's and then changes it. Returns that file, to remove
+/// once the shell exits.
+fn prompt_hint(command: &mut Command, shell: &str, name: &str) -> Option<PathBuf> {
+    let hint = format!("({}) ", name);
+    if cfg!(windows) {
+        command.env("PROMPT", format!("{}$P$G", hint));
+        return None;
+    }
+    let is_bash = Path::new(shell)
+        .file_name()
+        .is_some_and(|file| file == "bash");
+    if !is_bash {
+        let prompt = std::env::var("PS1").unwrap_or_else(|_| "$ ".to_owned());
+        command.env("PS1", format!("{}{}", hint, prompt));
+        return None;
+    }
+    let quoted = shlex::try_quote(&hint).ok()?;
+    let rc = format!("[ -f ~/.bashrc ] && . ~/.bashrc\nPS1={}\"$PS1\"\n", quoted);
+    let path = std::env::temp_dir().join(format!("envmaster-{}.bashrc", std::process::id()));
+    match std::fs::write(&path, rc) {
+        Ok(()) => {
+            command.arg("--rcfile").arg(&path);
+            Some(path)
+        }
+        Err(err) => {
+            warn!(
+                "couldn't write the prompt hint {} : {}",
+                path.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Starts the user's shell with the environment of a configuration, and exits with its status.
+/// Shells started from another envmaster shell are refused unless `nested` is set.
+pub fn subshell_command(
+    db_path: &Path,
+    target: Option<&str>,
+    nested: bool,
+    options: LaunchOptions,
+) {
+    if let Ok(active) = std::env::var(ACTIVE_VARIABLE) {
+        if !nested {
+            panic!(
+                "Already in the shell of {}, exit it first or pass --nested.",
+                active
+            );
+        }
+        warn!("starting a shell inside the shell of {}", active);
+    }
+    let mut conn = establish_connection(db_path);
+    let (id, name) = config_target(target);
+    let config = resolve_configuration(&mut conn, id, name);
+    drop(conn);
+    let name = &config.configuration.name;
+    let shell = user_shell();
+    let mut command = Command::new(&shell);
+    environment_setup(&config, get_environment(&config), &options)(&mut command);
+    command.env(ACTIVE_VARIABLE, name);
+    let rc = prompt_hint(&mut command, &shell, name);
+    info!(target: LAUNCH_TARGET, "shell {} with {}", shell, name);
+    println!("Entering {} with {}, exit to leave.", shell, name);
+    let status = command.status();
+    if let Some(rc) = rc {
+        let _ = std::fs::remove_file(rc);
+    }
+    let status = status.unwrap_or_else(|err| panic!("Couldn't start {} : {}", shell, err));
+    info!(target: LAUNCH_TARGET, "shell {} exited ({})", shell, status);
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// Applies the single instance policy of `config` when it's already running, returns whether
+/// the launch goes on.
+fn single_instance(conn: &mut SqliteConnection, config: &LinkedConfiguration) -> bool {
+    let Some(policy) = config.single_instance() else {
+        return true;
+    };
+    let running = registry::running(conn, Some(config.configuration.id)).unwrap();
+    if running.is_empty() {
+        return true;
+    }
+    let name = &config.configuration.name;
+    let pids: Vec<String> = running
+        .iter()
+        .map(|process| format!("{} (pid {})", process.name, process.pid))
+        .collect();
+    let pids = pids.join(", ");
+    match policy {
+        SINGLE_INSTANCE_REFUSE => panic!(
+            "{} is already running : {}. Stop it with `envmaster stop {}`.",
+            name, pids, name
+        ),
+        SINGLE_INSTANCE_NOTIFY => {
+            info!(target: LAUNCH_TARGET, "{} is already running : {}", name, pids);
+            println!("{} is already running : {}", name, pids);
+            false
+        }
+        SINGLE_INSTANCE_REPLACE => {
+            info!(target: LAUNCH_TARGET, "replacing {} : {}", name, pids);
+            registry::stop(conn, &running).unwrap();
+            println!("stopped {}", pids);
+            true
+        }
+        _ => panic!("Unknown single instance policy {} for {}", policy, name),
+    }
+}
+
+/// Stops and forgets the processes of `config` already started when the next one can't be, so a
+/// failed launch doesn't leave part of the configuration running, then exits.
+fn abort_launch<'a>(
+    db_path: &Path,
+    config: &LinkedConfiguration,
+    reason: String,
+    started: impl Iterator<Item = &'a mut Supervised>,
+) -> ! {
+    let mut records = vec![];
+    for process in started {
+        let _ = process.child.kill();
+        let _ = process.child.wait();
+        info!(target: LAUNCH_TARGET, "stopped {}", process.name);
+        println!("stopped {}", process.name);
+        records.extend(process.record);
+    }
+    registry::forget(db_path, &records);
+    error!(target: LAUNCH_TARGET, "launching {} failed : {}", config.configuration.name, reason);
+    panic!(
+        "The configuration {} couldn't be launched : {}.",
+        config.configuration.name, reason
+    );
+}
+
+/// Runs the hooks and starts the processes of `config`, recording them in the
+/// [`crate::registry`], then waits for the ones that aren't detached.
+pub fn launch_configuration(db_path: &Path, config: LinkedConfiguration, options: LaunchOptions) {
+    let mut conn = establish_connection(db_path);
+    // Released once every process is recorded, see `registry::lock_configuration`.
+    let lock = config
+        .single_instance()
+        .and_then(|_| registry::lock_configuration(db_path, config.configuration.id));
+    if !single_instance(&mut conn, &config) {
+        return;
+    }
+    let envs = get_environment(&config);
+    let search_path = launch_search_path(&envs, &config.unset_variables());
+    let prepare = environment_setup(&config, envs, &options);
+    let launches = get_launches(
+        &mut conn,
+        &config,
+        options.path.clone(),
+        search_path.as_deref(),
+    );
+    info!(
+        target: LAUNCH_TARGET,
+        "launching {} from {}",
+        config.configuration.name,
+        db_path.display()
+    );
+    run_hooks(&config, HOOK_PRE, &prepare);
+    let mut running = vec![];
+    // Detached processes aren't waited for, they are only kept to be stopped if a later one
+    // fails to start.
+    let mut detached = vec![];
+    for mut launch in launches {
+        let command = &mut launch.command;
+        prepare(command);
+        let detach = match launch.mode.as_str() {
+            "wait" => false,
+            "detach" => {
+                match create_launch_log(db_path, &config.configuration.name, &launch.name) {
+                    Ok((path, file)) => match file.try_clone() {
+                        Ok(err_file) => {
+                            command.stdin(Stdio::null()).stdout(file).stderr(err_file);
+                            info!("the output of {} goes to {}", launch.name, path.display());
+                        }
+                        Err(err) => {
+                            warn!("couldn't capture the output of {} : {}", launch.name, err)
+                        }
+                    },
+                    Err(err) => warn!("couldn't capture the output of {} : {}", launch.name, err),
+                }
+                true
+            }
+            mode => abort_launch(
+                db_path,
+                &config,
+                format!("{} has an unknown mode {}", launch.name, mode),
+                running.iter_mut().chain(detached.iter_mut()),
+            ),
+        };
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => abort_launch(
+                db_path,
+                &config,
+                format!("couldn't start {} : {}", launch.name, err),
+                running.iter_mut().chain(detached.iter_mut()),
+            ),
+        };
+        let verb = if detach { "detached" } else { "started" };
+        info!(target: LAUNCH_TARGET, "{} {} (pid {})", verb, launch.name, child.id());
+        let record = registry::record(
+            &mut conn,
+            config.configuration.id,
+            &launch.name,
+            child.id(),
+            &options,
+        );
+        let process = Supervised {
+            name: launch.name,
+            child,
+            stop_others: launch.stop_others,
+            record,
+        };
+        if detach {
+            detached.push(process);
+        } else {
+            running.push(process);
+        }
+    }
+    drop(lock);
+    drop(conn);
+    let waited = !running.is_empty();
+    supervise(db_path, running);
+    if waited {
+        run_hooks(&config, HOOK_POST, &prepare);
+    } else if !config.hooks(HOOK_POST).is_empty() {
+        warn!("nothing was waited for, the post-exit hooks were not run.");
+    }
+}
+
+/// Runs `line` with the system shell.
+fn shell_command(line: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = Command::new(shell);
+    command.args([flag, line]);
+    command
+}
+
+/// Runs the hooks of `stage` one after the other, printing their output. A failing hook stops
+/// everything when its policy is `abort`.
+fn run_hooks(cfg: &LinkedConfiguration, stage: &str, prepare: &dyn Fn(&mut Command)) {
+    for hook in cfg.hooks(stage) {
+        let mut command = shell_command(&hook.command);
+        prepare(&mut command);
+        let failure = match command.output() {
+            Ok(output) => {
+                info!(target: LAUNCH_TARGET, "{} hook `{}`", stage, hook.command);
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    info!(target: LAUNCH_TARGET, "    {}", line);
+                    println!("[{} hook] {}", stage, line);
+                }
+                for line in String::from_utf8_lossy(&output.stderr).lines() {
+                    info!(target: LAUNCH_TARGET, "    {}", line);
+                    eprintln!("[{} hook] {}", stage, line);
+                }
+                (!output.status.success()).then(|| output.status.to_string())
+            }
+            Err(err) => Some(err.to_string()),
+        };
+        let Some(failure) = failure else {
+            continue;
+        };
+        match hook.on_failure.as_str() {
+            "ignore" => {}
+            "warn" => warn!("the {} hook `{}` failed : {}", stage, hook.command, failure),
+            _ => {
+                error!(
+                    target: LAUNCH_TARGET,
+                    "{} hook `{}` failed : {}", stage, hook.command, failure
+                );
+                panic!(
+                    "The {} hook `{}` of {} failed : {}",
+                    stage, hook.command, cfg.configuration.name, failure
+                )
+            }
         }
     }
 }